@@ -58,6 +58,15 @@ impl Color {
     }
   }
 
+  // 各成分を [0, 1] の範囲に直したベクトル(アルファは捨てる)
+  pub fn to_vec3(&self) -> Vec3 {
+    Vec3::new(
+      self.r as f64 / 255.0,
+      self.g as f64 / 255.0,
+      self.b as f64 / 255.0,
+    )
+  }
+
   pub fn from_vec3_gamma(from: Vec3, alpha: u8, gamma_factor: f64) -> Self {
     let inv_gamma_factor = 1.0 / gamma_factor;
     let x = from.get_x().powf(inv_gamma_factor);
@@ -71,3 +80,21 @@ impl Color {
     }
   }
 }
+
+// sRGB の規格どおりの伝達関数。線形の値 [0, 1] を表示用の値にする(暗部は線形)
+pub fn srgb_encode(x: f64) -> f64 {
+  if x <= 0.0031308 {
+    12.92 * x
+  } else {
+    1.055 * x.powf(1.0 / 2.4) - 0.055
+  }
+}
+
+// srgb_encode の逆。sRGB で保存された画素値 [0, 1] を線形の値に戻す
+pub fn srgb_decode(x: f64) -> f64 {
+  if x <= 0.04045 {
+    x / 12.92
+  } else {
+    ((x + 0.055) / 1.055).powf(2.4)
+  }
+}
@@ -1,7 +1,5 @@
-use once_cell::sync::Lazy;
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
-use std::sync::Mutex;
 pub fn clamp<T>(v: T, min: T, max: T) -> T
 where
   T: std::cmp::Ord,
@@ -9,21 +7,54 @@ where
   v.max(min).min(max)
 }
 
-static RAND_GEN: Lazy<Mutex<StdRng>> = Lazy::new(|| {
+// 描画に使う乱数生成器。シードが同じなら同じ乱数列を返す
+pub type RandGen = StdRng;
+
+pub fn new_rand_gen(seed: u64) -> RandGen {
+  StdRng::seed_from_u64(seed)
+}
+
+// シードが指定されなかった場合に使う、現在時刻から作ったシード
+pub fn seed_from_time() -> u64 {
   let now = std::time::SystemTime::now();
   let unixtime = now
     .duration_since(std::time::SystemTime::UNIX_EPOCH)
     .expect("back to the future")
     .as_nanos();
-  Mutex::new(StdRng::seed_from_u64(unixtime as u64))
-});
+  unixtime as u64
+}
+
+// シードと番号を混ぜて独立したシードを作る(SplitMix64)
+// ピクセルごとに乱数生成器を作ることで、スレッド数や描画順によらず同じ結果にする
+pub fn mix_seed(seed: u64, index: u64) -> u64 {
+  let mut z = seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+  z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+  z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  z ^ (z >> 31)
+}
+
+pub fn get_uniform_random(rng: &mut RandGen) -> f64 {
+  rng.gen()
+}
 
-pub fn get_uniform_random() -> f64 {
-  let x: f64 = RAND_GEN.lock().unwrap().gen();
-  x
+pub fn get_random_in_range(rng: &mut RandGen, lower: f64, upper: f64) -> f64 {
+  rng.gen_range(lower..upper)
 }
 
-pub fn get_random_in_range(lower: f64, upper: f64) -> f64 {
-  let x: f64 = RAND_GEN.lock().unwrap().gen_range(lower..upper);
-  x
+#[cfg(test)]
+mod math_test {
+  use super::*;
+  #[test]
+  fn test_seeded_random() {
+    let mut rng1 = new_rand_gen(42);
+    let mut rng2 = new_rand_gen(42);
+    let mut rng3 = new_rand_gen(mix_seed(42, 1));
+    let v1: Vec<f64> = (0..10).map(|_| get_uniform_random(&mut rng1)).collect();
+    let v2: Vec<f64> = (0..10).map(|_| get_uniform_random(&mut rng2)).collect();
+    let v3: Vec<f64> = (0..10).map(|_| get_uniform_random(&mut rng3)).collect();
+    assert_eq!(v1, v2);
+    assert_ne!(v1, v3);
+    assert_ne!(mix_seed(42, 1), mix_seed(42, 2));
+  }
 }
@@ -0,0 +1,230 @@
+use std::ops::Mul;
+
+use crate::base::vec::Vec3;
+
+// 同次座標の 4x4 行列。m[行][列] で、ベクトルは列ベクトルとして右から掛ける
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+  m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+  pub fn new(m: [[f64; 4]; 4]) -> Self {
+    Mat4 { m }
+  }
+
+  pub fn identity() -> Self {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+      row[i] = 1.0;
+    }
+    Mat4 { m }
+  }
+
+  pub fn translation(t: &Vec3) -> Self {
+    let mut ret = Self::identity();
+    ret.m[0][3] = t.get_x();
+    ret.m[1][3] = t.get_y();
+    ret.m[2][3] = t.get_z();
+    ret
+  }
+
+  pub fn scale(s: &Vec3) -> Self {
+    let mut ret = Self::identity();
+    ret.m[0][0] = s.get_x();
+    ret.m[1][1] = s.get_y();
+    ret.m[2][2] = s.get_z();
+    ret
+  }
+
+  // axis を軸に angle(ラジアン)だけ回す回転行列
+  pub fn rotation(axis: &Vec3, angle: f64) -> Self {
+    let k = axis.normalize();
+    let (x, y, z) = k.to_tuple();
+    let (sin, cos) = angle.sin_cos();
+    let c = 1.0 - cos;
+    Mat4 {
+      m: [
+        [
+          cos + x * x * c,
+          x * y * c - z * sin,
+          x * z * c + y * sin,
+          0.0,
+        ],
+        [
+          y * x * c + z * sin,
+          cos + y * y * c,
+          y * z * c - x * sin,
+          0.0,
+        ],
+        [
+          z * x * c - y * sin,
+          z * y * c + x * sin,
+          cos + z * z * c,
+          0.0,
+        ],
+        [0.0, 0.0, 0.0, 1.0],
+      ],
+    }
+  }
+
+  pub fn get(&self, row: usize, col: usize) -> f64 {
+    self.m[row][col]
+  }
+
+  pub fn transpose(&self) -> Self {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+      for (j, v) in row.iter_mut().enumerate() {
+        *v = self.m[j][i];
+      }
+    }
+    Mat4 { m }
+  }
+
+  // 部分ピボット選択付きのガウス・ジョルダン法で逆行列を求める
+  // 正則でないか、NaN や無限大を含む場合は None
+  pub fn inverse(&self) -> Option<Self> {
+    if !self.m.iter().flatten().all(|v| v.is_finite()) {
+      return None;
+    }
+    let mut a = self.m;
+    let mut inv = Self::identity().m;
+    for col in 0..4 {
+      let pivot = (col..4)
+        .max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))
+        .unwrap();
+      if a[pivot][col].abs() < 1.0e-12 {
+        return None;
+      }
+      a.swap(col, pivot);
+      inv.swap(col, pivot);
+
+      let p = a[col][col];
+      for j in 0..4 {
+        a[col][j] /= p;
+        inv[col][j] /= p;
+      }
+      for row in 0..4 {
+        if row == col {
+          continue;
+        }
+        let f = a[row][col];
+        for j in 0..4 {
+          a[row][j] -= f * a[col][j];
+          inv[row][j] -= f * inv[col][j];
+        }
+      }
+    }
+    Some(Mat4 { m: inv })
+  }
+
+  // 左上 3x3(平行移動を除いた線形部分)の行列式
+  pub fn linear_determinant(&self) -> f64 {
+    let m = &self.m;
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+      - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+      + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+  }
+
+  // 点として変換する(平行移動が効く)
+  pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+    let (x, y, z) = p.to_tuple();
+    let m = &self.m;
+    let w = m[3][0] * x + m[3][1] * y + m[3][2] * z + m[3][3];
+    Vec3::new(
+      m[0][0] * x + m[0][1] * y + m[0][2] * z + m[0][3],
+      m[1][0] * x + m[1][1] * y + m[1][2] * z + m[1][3],
+      m[2][0] * x + m[2][1] * y + m[2][2] * z + m[2][3],
+    ) / w
+  }
+
+  // 方向ベクトルとして変換する(平行移動は効かない)
+  pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+    let (x, y, z) = v.to_tuple();
+    let m = &self.m;
+    Vec3::new(
+      m[0][0] * x + m[0][1] * y + m[0][2] * z,
+      m[1][0] * x + m[1][1] * y + m[1][2] * z,
+      m[2][0] * x + m[2][1] * y + m[2][2] * z,
+    )
+  }
+}
+
+impl Mul for Mat4 {
+  type Output = Mat4;
+  fn mul(self, other: Mat4) -> Mat4 {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+      for (j, v) in row.iter_mut().enumerate() {
+        *v = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+      }
+    }
+    Mat4 { m }
+  }
+}
+
+#[cfg(test)]
+mod matrix_test {
+  use super::*;
+
+  fn assert_near(a: &Vec3, b: &Vec3) {
+    assert!((*a - *b).norm() < 1e-9, "{:?} != {:?}", a, b);
+  }
+
+  #[test]
+  fn test_transform() {
+    let p = Vec3::new(1.0, 2.0, 3.0);
+    let t = Mat4::translation(&Vec3::new(1.0, 0.0, -1.0));
+    assert_eq!(t.transform_point(&p), Vec3::new(2.0, 2.0, 2.0));
+    assert_eq!(t.transform_vector(&p), p);
+
+    let s = Mat4::scale(&Vec3::new(2.0, 3.0, 4.0));
+    assert_eq!(s.transform_point(&p), Vec3::new(2.0, 6.0, 12.0));
+
+    let r = Mat4::rotation(&Vec3::new(0.0, 0.0, 1.0), std::f64::consts::FRAC_PI_2);
+    assert_near(&r.transform_point(&p), &Vec3::new(-2.0, 1.0, 3.0));
+    assert_near(
+      &r.transform_vector(&p),
+      &p.rotate(&Vec3::new(0.0, 0.0, 1.0), std::f64::consts::FRAC_PI_2),
+    );
+  }
+
+  #[test]
+  fn test_mul() {
+    // 右にある行列から順に適用される
+    let t = Mat4::translation(&Vec3::new(1.0, 0.0, 0.0));
+    let s = Mat4::scale(&Vec3::from_one(2.0));
+    let p = Vec3::new(1.0, 1.0, 1.0);
+    assert_eq!((t * s).transform_point(&p), Vec3::new(3.0, 2.0, 2.0));
+    assert_eq!((s * t).transform_point(&p), Vec3::new(4.0, 2.0, 2.0));
+  }
+
+  #[test]
+  fn test_inverse() {
+    let m = Mat4::translation(&Vec3::new(1.0, -2.0, 3.0))
+      * Mat4::rotation(&Vec3::new(1.0, 1.0, 0.0), 0.7)
+      * Mat4::scale(&Vec3::new(2.0, 0.5, 3.0));
+    let inv = m.inverse().unwrap();
+    let p = Vec3::new(0.3, -4.0, 2.5);
+    assert_near(&inv.transform_point(&m.transform_point(&p)), &p);
+    let id = m * inv;
+    for i in 0..4 {
+      for j in 0..4 {
+        let expected = if i == j { 1.0 } else { 0.0 };
+        assert!((id.get(i, j) - expected).abs() < 1e-9);
+      }
+    }
+    assert!(Mat4::scale(&Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    // 回転と平行移動は体積を変えない
+    assert!((m.linear_determinant() - 3.0).abs() < 1e-9);
+    assert!((inv.linear_determinant() - 1.0 / 3.0).abs() < 1e-9);
+    assert!(Mat4::scale(&Vec3::new(1.0, f64::NAN, 1.0))
+      .inverse()
+      .is_none());
+    assert!(Mat4::translation(&Vec3::new(f64::INFINITY, 0.0, 0.0))
+      .inverse()
+      .is_none());
+    assert_eq!(m.transpose().transpose(), m);
+  }
+}
@@ -1,2 +1,6 @@
 pub mod color;
-pub mod math;pub mod vec;
+pub mod math;
+pub mod matrix;
+pub mod onb;
+pub mod perlin;
+pub mod vec;
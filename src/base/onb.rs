@@ -0,0 +1,74 @@
+use crate::base::vec::Vec3;
+
+// w を Z 軸とする正規直交基底。法線まわりの局所座標から世界座標に戻すのに使う
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+  u: Vec3,
+  v: Vec3,
+  w: Vec3,
+}
+
+impl Onb {
+  pub fn from_w(n: &Vec3) -> Self {
+    let w = n.normalize();
+    // w とほぼ平行にならない軸を選んで外積を取る
+    let a = if w.get_x().abs() > 0.9 {
+      Vec3::new(0.0, 1.0, 0.0)
+    } else {
+      Vec3::new(1.0, 0.0, 0.0)
+    };
+    let v = w.cross(&a).normalize();
+    let u = v.cross(&w);
+    Self { u, v, w }
+  }
+
+  pub fn u(&self) -> Vec3 {
+    self.u
+  }
+
+  pub fn v(&self) -> Vec3 {
+    self.v
+  }
+
+  pub fn w(&self) -> Vec3 {
+    self.w
+  }
+
+  // 世界座標の向きを局所座標 (x, y, z) に直す
+  pub fn to_local(&self, a: &Vec3) -> Vec3 {
+    Vec3::new(a.dot(&self.u), a.dot(&self.v), a.dot(&self.w))
+  }
+
+  // 局所座標 (x, y, z) の向きを世界座標に直す
+  pub fn local(&self, a: &Vec3) -> Vec3 {
+    self.u * a.get_x() + self.v * a.get_y() + self.w * a.get_z()
+  }
+}
+
+#[cfg(test)]
+mod onb_test {
+  use super::*;
+
+  #[test]
+  fn test_orthonormal() {
+    for n in [
+      Vec3::new(0.0, 0.0, 1.0),
+      Vec3::new(1.0, 0.0, 0.0),
+      Vec3::new(-1.0, 2.0, 3.0),
+    ] {
+      let onb = Onb::from_w(&n);
+      assert!((onb.w() - n.normalize()).norm() < 1e-12);
+      for e in [onb.u(), onb.v(), onb.w()] {
+        assert!((e.norm() - 1.0).abs() < 1e-12);
+      }
+      assert!(onb.u().dot(&onb.v()).abs() < 1e-12);
+      assert!(onb.u().dot(&onb.w()).abs() < 1e-12);
+      assert!(onb.v().dot(&onb.w()).abs() < 1e-12);
+      // 右手系になっている
+      assert!((onb.u().cross(&onb.v()) - onb.w()).norm() < 1e-12);
+      assert!((onb.local(&Vec3::new(0.0, 0.0, 2.0)) - onb.w() * 2.0).norm() < 1e-12);
+      let a = Vec3::new(0.3, -0.4, 1.2);
+      assert!((onb.local(&onb.to_local(&a)) - a).norm() < 1e-12);
+    }
+  }
+}
@@ -0,0 +1,133 @@
+use rand::seq::SliceRandom;
+
+use crate::base::math::{get_random_in_range, new_rand_gen};
+use crate::base::vec::Vec3;
+
+const POINT_COUNT: usize = 256;
+
+// 格子点に乱数の勾配ベクトルを置いたパーリンノイズ
+// シードが同じなら同じ模様になる
+pub struct Perlin {
+  gradients: Vec<Vec3>,
+  perm_x: Vec<usize>,
+  perm_y: Vec<usize>,
+  perm_z: Vec<usize>,
+}
+
+impl Perlin {
+  pub fn new(seed: u64) -> Self {
+    let mut rng = new_rand_gen(seed);
+    let gradients = (0..POINT_COUNT)
+      .map(|_| {
+        Vec3::new(
+          get_random_in_range(&mut rng, -1.0, 1.0),
+          get_random_in_range(&mut rng, -1.0, 1.0),
+          get_random_in_range(&mut rng, -1.0, 1.0),
+        )
+        .normalize()
+      })
+      .collect();
+    let mut gen_perm = || {
+      let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+      perm.shuffle(&mut rng);
+      perm
+    };
+    let perm_x = gen_perm();
+    let perm_y = gen_perm();
+    let perm_z = gen_perm();
+    Self {
+      gradients,
+      perm_x,
+      perm_y,
+      perm_z,
+    }
+  }
+
+  // おおよそ -1 から 1 の値。格子点では 0 になる
+  pub fn noise(&self, p: &Vec3) -> f64 {
+    let fract = |x: f64| x - x.floor();
+    let (u, v, w) = (fract(p.get_x()), fract(p.get_y()), fract(p.get_z()));
+    let i = p.get_x().floor() as i64;
+    let j = p.get_y().floor() as i64;
+    let k = p.get_z().floor() as i64;
+
+    // エルミート補間で格子の境目を滑らかにする
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+    let (uu, vv, ww) = (smooth(u), smooth(v), smooth(w));
+    let mut accum = 0.0;
+    for di in 0..2 {
+      for dj in 0..2 {
+        for dk in 0..2 {
+          let gradient = self.gradient(i + di, j + dj, k + dk);
+          let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+          let weight = Vec3::new(u - fi, v - fj, w - fk);
+          accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+            * (fj * vv + (1.0 - fj) * (1.0 - vv))
+            * (fk * ww + (1.0 - fk) * (1.0 - ww))
+            * gradient.dot(&weight);
+        }
+      }
+    }
+    accum
+  }
+
+  // 周波数を2倍、振幅を半分にしながら octaves 回ノイズの絶対値を足し合わせる
+  pub fn turbulence(&self, p: &Vec3, octaves: usize) -> f64 {
+    let mut accum = 0.0;
+    let mut p = *p;
+    let mut weight = 1.0;
+    for _ in 0..octaves {
+      accum += weight * self.noise(&p).abs();
+      weight *= 0.5;
+      p = p * 2.0;
+    }
+    accum
+  }
+
+  fn gradient(&self, i: i64, j: i64, k: i64) -> Vec3 {
+    let mask = POINT_COUNT as i64 - 1;
+    let index = self.perm_x[(i & mask) as usize]
+      ^ self.perm_y[(j & mask) as usize]
+      ^ self.perm_z[(k & mask) as usize];
+    self.gradients[index]
+  }
+}
+
+impl std::fmt::Debug for Perlin {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Perlin").finish()
+  }
+}
+
+#[cfg(test)]
+mod perlin_test {
+  use super::*;
+
+  #[test]
+  fn test_noise() {
+    let perlin = Perlin::new(1);
+    let same = Perlin::new(1);
+    let other = Perlin::new(2);
+    let p = Vec3::new(0.3, 1.7, -2.2);
+    assert_eq!(perlin.noise(&p), same.noise(&p));
+    assert_ne!(perlin.noise(&p), other.noise(&p));
+    assert_eq!(perlin.noise(&Vec3::new(3.0, -1.0, 5.0)), 0.0);
+
+    // 近い点では近い値になり、値は -1 から 1 に収まる
+    let q = p + Vec3::from_one(1.0e-4);
+    assert!((perlin.noise(&p) - perlin.noise(&q)).abs() < 1.0e-3);
+    for n in 0..1000 {
+      let x = n as f64 * 0.137;
+      let value = perlin.noise(&Vec3::new(x, x * 0.61, -x * 1.3));
+      assert!((-1.0..=1.0).contains(&value));
+    }
+  }
+
+  #[test]
+  fn test_turbulence() {
+    let perlin = Perlin::new(1);
+    let p = Vec3::new(0.3, 1.7, -2.2);
+    assert_eq!(perlin.turbulence(&p, 1), perlin.noise(&p).abs());
+    assert!(perlin.turbulence(&p, 7) >= perlin.turbulence(&p, 1));
+  }
+}
@@ -1,6 +1,6 @@
 use std::ops::{Add, Div, Mul, Sub};
 
-use crate::base::math::get_random_in_range;
+use crate::base::math::{get_random_in_range, get_uniform_random, RandGen};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Vec3 {
@@ -26,7 +26,7 @@ impl Vec3 {
     Self::from_one(0.0)
   }
 
-  pub fn gen_random_vector() -> Self {
+  pub fn gen_random_vector(rng: &mut RandGen) -> Self {
     //let unix_time = std::time::SystemTime::now()
     //  .duration_since(std::time::SystemTime::UNIX_EPOCH)
     //  .expect("failed to get UNIX time")
@@ -35,21 +35,34 @@ impl Vec3 {
     //let mut rng = StdRng::seed_from_u64((unix_time & (std::u64::MAX - 1) as u128) as u64);
 
     Vec3 {
-      x: get_random_in_range(-1.0, 1.0),
-      y: get_random_in_range(-1.0, 1.0),
-      z: get_random_in_range(-1.0, 1.0),
+      x: get_random_in_range(rng, -1.0, 1.0),
+      y: get_random_in_range(rng, -1.0, 1.0),
+      z: get_random_in_range(rng, -1.0, 1.0),
     }
   }
 
-  pub fn gen_random_vector_in_unit_shpere() -> Self {
+  pub fn gen_random_vector_in_unit_shpere(rng: &mut RandGen) -> Self {
     let mut p: Vec3 = Vec3::new(1000.0, 0.0, 0.0);
     // 単位円の内部にある(=長さが1^2以下)のベクトルが生成されるまでサンプリング
     while p.norm() >= 1.0 {
-      p = Self::gen_random_vector();
+      p = Self::gen_random_vector(rng);
     }
     p
   }
 
+  // Z 軸を法線とする半球上で、cosθ に比例する密度の単位ベクトル
+  pub fn gen_random_cosine_direction(rng: &mut RandGen) -> Self {
+    let r1 = get_uniform_random(rng);
+    let r2 = get_uniform_random(rng);
+    let phi = 2.0 * std::f64::consts::PI * r1;
+    let sin_theta = r2.sqrt();
+    Vec3 {
+      x: phi.cos() * sin_theta,
+      y: phi.sin() * sin_theta,
+      z: (1.0 - r2).sqrt(),
+    }
+  }
+
   pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
     let dot = 2.0 * v.dot(n);
     v.clone() - n.clone() * dot
@@ -125,6 +138,41 @@ impl Vec3 {
     self.z
   }
 
+  // 軸番号(0: x, 1: y, 2: z)で成分を取り出す
+  pub fn get(&self, axis: usize) -> f64 {
+    match axis {
+      0 => self.x,
+      1 => self.y,
+      2 => self.z,
+      _ => panic!("invalid axis {}", axis),
+    }
+  }
+
+  // 成分ごとの最小値
+  pub fn min(&self, other: &Self) -> Self {
+    Vec3 {
+      x: self.x.min(other.x),
+      y: self.y.min(other.y),
+      z: self.z.min(other.z),
+    }
+  }
+
+  // 成分ごとの最大値
+  pub fn max(&self, other: &Self) -> Self {
+    Vec3 {
+      x: self.x.max(other.x),
+      y: self.y.max(other.y),
+      z: self.z.max(other.z),
+    }
+  }
+
+  // axis を軸に angle(ラジアン)だけ回転したベクトル(ロドリゲスの回転公式)
+  pub fn rotate(&self, axis: &Self, angle: f64) -> Self {
+    let k = axis.normalize();
+    let (sin, cos) = angle.sin_cos();
+    *self * cos + k.cross(self) * sin + k * (k.dot(self) * (1.0 - cos))
+  }
+
   pub fn lerp(t: f64, a: &Self, b: &Self) -> Self {
     assert!(0.0 <= t && t <= 1.0);
     a.dir(1.0 - t) + b.dir(t)
@@ -206,6 +254,7 @@ impl Mul<Vec3> for Vec3 {
 #[cfg(test)]
 mod test {
   use super::*;
+  use crate::base::math::new_rand_gen;
   #[test]
   fn test_norm() {
     assert_eq!(Vec3::new(1.0, 1.0, 1.0).norm(), 3.0_f64.sqrt());
@@ -220,7 +269,7 @@ mod test {
 
   #[test]
   fn test_random_vector() {
-    let random = Vec3::gen_random_vector_in_unit_shpere();
+    let random = Vec3::gen_random_vector_in_unit_shpere(&mut new_rand_gen(0));
     assert_eq!(random.norm() <= 1.0, true);
   }
 
@@ -244,6 +293,24 @@ mod test {
     assert_eq!(v1.cross(&v2), Vec3::new(-3.0, 6.0, -3.0));
   }
 
+  #[test]
+  fn test_min_max() {
+    let v1 = Vec3::new(1.0, 5.0, -3.0);
+    let v2 = Vec3::new(2.0, 4.0, -6.0);
+    assert_eq!(v1.min(&v2), Vec3::new(1.0, 4.0, -6.0));
+    assert_eq!(v1.max(&v2), Vec3::new(2.0, 5.0, -3.0));
+    assert_eq!(v1.get(1), 5.0);
+  }
+
+  #[test]
+  fn test_rotate() {
+    let v = Vec3::new(1.0, 0.0, 0.0);
+    let r = v.rotate(&Vec3::new(0.0, 0.0, 2.0), std::f64::consts::FRAC_PI_2);
+    assert!((r - Vec3::new(0.0, 1.0, 0.0)).norm() < 1e-12);
+    let r = v.rotate(&Vec3::new(0.0, 1.0, 0.0), std::f64::consts::PI);
+    assert!((r - Vec3::new(-1.0, 0.0, 0.0)).norm() < 1e-12);
+  }
+
   #[test]
   fn test_add() {
     let v1 = Vec3::new(1.0, 1.0, 1.0);
@@ -1,5 +1,6 @@
 use crate::config::render_config::RenderConfig;
 use crate::config::scene_config::SceneConfig;
+use crate::scene::output::OutputFormat;
 use serde::{Deserialize, Serialize};
 use serde_yaml;
 use serde_yaml::Error;
@@ -8,6 +9,9 @@ use std::path::Path;
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
   output_name: String,
+  // 書き出す画像の形式。省略すると output_name の拡張子から決める
+  #[serde(default)]
+  format: Option<OutputFormatConfig>,
   render: RenderConfig,
   scene: SceneConfig,
 }
@@ -23,6 +27,10 @@ impl Config {
     self.output_name.as_str()
   }
 
+  pub fn output_format(&self) -> Option<OutputFormat> {
+    self.format.map(|f| f.to_output_format())
+  }
+
   pub fn render_config(&self) -> &RenderConfig {
     &self.render
   }
@@ -32,6 +40,29 @@ impl Config {
   }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum OutputFormatConfig {
+  #[serde(rename(serialize = "png", deserialize = "png"))]
+  Png,
+  #[serde(rename(serialize = "jpeg", deserialize = "jpeg"))]
+  Jpeg,
+  #[serde(rename(serialize = "exr", deserialize = "exr"))]
+  Exr,
+  #[serde(rename(serialize = "hdr", deserialize = "hdr"))]
+  Hdr,
+}
+
+impl OutputFormatConfig {
+  pub fn to_output_format(&self) -> OutputFormat {
+    match self {
+      OutputFormatConfig::Png => OutputFormat::Png,
+      OutputFormatConfig::Jpeg => OutputFormat::Jpeg,
+      OutputFormatConfig::Exr => OutputFormat::Exr,
+      OutputFormatConfig::Hdr => OutputFormat::Hdr,
+    }
+  }
+}
+
 #[cfg(test)]
 mod test {
   use super::*;
@@ -1,11 +1,211 @@
 use serde::{Deserialize, Serialize};
 
+use crate::scene::filter::Filter;
+use crate::scene::integrator::Integrator;
+use crate::scene::sampler::SamplerKind;
+use crate::scene::tone_map::{ToneMapOperator, ToneMapping, TransferFunction};
+use crate::scene::DEFAULT_ROULETTE_DEPTH;
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub struct RenderConfig {
   pub width: u32,
   pub height: u32,
+  // 1画素あたりのサンプル数。max_samples を指定した場合は1回の描画で足すサンプル数になる
   pub sampling: u32,
+  // 適応的サンプリングの設定。sampling ずつサンプルを足していき、
+  // 明るさの相対誤差が noise_threshold 以下になった画素と max_samples に達した画素から止める
+  // time_limit(秒)を過ぎたらその回の描画を終えた時点で打ち切る
+  // noise_threshold と time_limit は max_samples がないと効かない(警告を出して1回だけ描画する)
+  // sampling が 0 の場合は1回に1サンプルずつ足す。max_samples がない場合は sampling を 1 以上にする
+  #[serde(default)]
+  pub max_samples: Option<u32>,
+  #[serde(default)]
+  pub noise_threshold: Option<f64>,
+  #[serde(default)]
+  pub time_limit: Option<f64>,
   pub max_scatter_depth: u32,
+  // この回数以上散乱した暗い経路をロシアンルーレットで確率的に打ち切る
+  // max_scatter_depth 以上にすると打ち切らない
+  #[serde(default = "default_roulette_depth")]
+  pub roulette_depth: u32,
+  // false にするとBVHを使わず全物体を線形に探索する(デバッグ用)
+  #[serde(default = "default_use_bvh")]
+  pub use_bvh: bool,
+  // 描画に使うスレッド数。0 の場合は利用可能なCPU数に合わせる
+  #[serde(default)]
+  pub threads: usize,
+  // 乱数のシード。指定しなければ描画のたびに現在時刻から決める
+  #[serde(default)]
+  pub seed: Option<u64>,
+  // 光線追跡の方法。mis にすると光源を直接サンプリングして小さな光源のノイズを減らす
+  #[serde(default)]
+  pub integrator: IntegratorConfig,
+  // 画素内の位置とレンズ上の位置の選び方
+  #[serde(default)]
+  pub sampler: SamplerConfig,
+  // サンプルから画素の色を組み立てるフィルタ。radius(画素単位)を省略するとフィルタごとの既定値
+  #[serde(default)]
+  pub filter: FilterConfig,
+  // 8bit の画像にするときの露出補正(EV)、トーンマッピング、伝達関数
+  // 既定では露出 0 で 1 を超える明るさを切り詰め、ガンマ 2.2 をかける
+  #[serde(default)]
+  pub exposure: f64,
+  #[serde(default)]
+  pub tone_map: ToneMapConfig,
+  #[serde(default)]
+  pub transfer: TransferConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum SamplerConfig {
+  #[default]
+  #[serde(rename(serialize = "random", deserialize = "random"))]
+  Random,
+  #[serde(rename(serialize = "stratified", deserialize = "stratified"))]
+  Stratified,
+  #[serde(rename(serialize = "halton", deserialize = "halton"))]
+  Halton,
+}
+
+impl SamplerConfig {
+  pub fn to_sampler_kind(&self) -> SamplerKind {
+    match self {
+      SamplerConfig::Random => SamplerKind::Random,
+      SamplerConfig::Stratified => SamplerKind::Stratified,
+      SamplerConfig::Halton => SamplerKind::Halton,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum FilterConfig {
+  #[serde(rename(serialize = "box", deserialize = "box"))]
+  Box {
+    #[serde(default)]
+    radius: Option<f64>,
+  },
+  #[serde(rename(serialize = "tent", deserialize = "tent"))]
+  Tent {
+    #[serde(default)]
+    radius: Option<f64>,
+  },
+  #[serde(rename(serialize = "gaussian", deserialize = "gaussian"))]
+  Gaussian {
+    #[serde(default)]
+    radius: Option<f64>,
+  },
+  #[serde(rename(serialize = "mitchell", deserialize = "mitchell"))]
+  Mitchell {
+    #[serde(default)]
+    radius: Option<f64>,
+  },
+  #[serde(rename(serialize = "lanczos", deserialize = "lanczos"))]
+  Lanczos {
+    #[serde(default)]
+    radius: Option<f64>,
+  },
+}
+
+impl Default for FilterConfig {
+  fn default() -> Self {
+    FilterConfig::Box { radius: None }
+  }
+}
+
+impl FilterConfig {
+  pub fn to_filter(&self) -> Filter {
+    match *self {
+      FilterConfig::Box { radius } => Filter::Box {
+        radius: radius.unwrap_or(0.5),
+      },
+      FilterConfig::Tent { radius } => Filter::Tent {
+        radius: radius.unwrap_or(1.0),
+      },
+      FilterConfig::Gaussian { radius } => Filter::Gaussian {
+        radius: radius.unwrap_or(1.5),
+      },
+      FilterConfig::Mitchell { radius } => Filter::Mitchell {
+        radius: radius.unwrap_or(2.0),
+      },
+      FilterConfig::Lanczos { radius } => Filter::Lanczos {
+        radius: radius.unwrap_or(2.0),
+      },
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum ToneMapConfig {
+  #[default]
+  #[serde(rename(serialize = "clamp", deserialize = "clamp"))]
+  Clamp,
+  #[serde(rename(serialize = "reinhard", deserialize = "reinhard"))]
+  Reinhard,
+  #[serde(rename(serialize = "aces", deserialize = "aces"), alias = "filmic")]
+  Aces,
+  #[serde(rename(serialize = "agx", deserialize = "agx"))]
+  Agx,
+}
+
+impl ToneMapConfig {
+  pub fn to_operator(&self) -> ToneMapOperator {
+    match self {
+      ToneMapConfig::Clamp => ToneMapOperator::Clamp,
+      ToneMapConfig::Reinhard => ToneMapOperator::Reinhard,
+      ToneMapConfig::Aces => ToneMapOperator::Aces,
+      ToneMapConfig::Agx => ToneMapOperator::Agx,
+    }
+  }
+}
+
+// srgb か、gamma: 2.2 のようにガンマ値を指定する
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum TransferConfig {
+  #[serde(rename(serialize = "gamma", deserialize = "gamma"))]
+  Gamma(f64),
+  #[serde(rename(serialize = "srgb", deserialize = "srgb"))]
+  Srgb,
+}
+
+impl Default for TransferConfig {
+  fn default() -> Self {
+    TransferConfig::Gamma(2.2)
+  }
+}
+
+impl TransferConfig {
+  pub fn to_transfer_function(&self) -> TransferFunction {
+    match *self {
+      TransferConfig::Gamma(gamma) => TransferFunction::Gamma(gamma),
+      TransferConfig::Srgb => TransferFunction::Srgb,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum IntegratorConfig {
+  #[default]
+  #[serde(rename(serialize = "path", deserialize = "path"))]
+  Path,
+  #[serde(rename(serialize = "mis", deserialize = "mis"))]
+  Mis,
+}
+
+impl IntegratorConfig {
+  pub fn to_integrator(&self) -> Integrator {
+    match self {
+      IntegratorConfig::Path => Integrator::Path,
+      IntegratorConfig::Mis => Integrator::Mis,
+    }
+  }
+}
+
+fn default_use_bvh() -> bool {
+  true
+}
+
+fn default_roulette_depth() -> u32 {
+  DEFAULT_ROULETTE_DEPTH
 }
 
 impl RenderConfig {
@@ -14,7 +214,28 @@ impl RenderConfig {
       width: 680,
       height: 460,
       sampling: 1,
+      max_samples: None,
+      noise_threshold: None,
+      time_limit: None,
       max_scatter_depth: 50,
+      roulette_depth: default_roulette_depth(),
+      use_bvh: default_use_bvh(),
+      threads: 0,
+      seed: None,
+      integrator: IntegratorConfig::default(),
+      sampler: SamplerConfig::default(),
+      filter: FilterConfig::default(),
+      exposure: 0.0,
+      tone_map: ToneMapConfig::default(),
+      transfer: TransferConfig::default(),
+    }
+  }
+
+  pub fn tone_mapping(&self) -> ToneMapping {
+    ToneMapping {
+      exposure: self.exposure,
+      operator: self.tone_map.to_operator(),
+      transfer: self.transfer.to_transfer_function(),
     }
   }
 }
@@ -1,16 +1,95 @@
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
 
-use crate::base::math::{get_random_in_range, get_uniform_random};
+use crate::base::math::{get_random_in_range, get_uniform_random, RandGen};
+use crate::base::matrix::Mat4;
+use crate::base::perlin::Perlin;
 use crate::base::vec::Vec3;
+use crate::object::cuboid::Cuboid;
 use crate::object::material::Material;
+use crate::object::mesh::Mesh;
+use crate::object::obj_loader::load_obj;
 use crate::object::shape::Shape;
 use crate::object::sphere::Sphere;
+use crate::object::texture::{ColorRamp, ImageTexture, NoisePattern, Texture};
+use crate::object::transform::Transformed;
+use crate::object::triangle::Triangle;
+use crate::scene::background::Background;
+use crate::scene::environment_map::EnvironmentMap;
+use crate::scene::light::Light;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SceneConfig {
   pub objects: Vec<ObjectConfig>,
   #[serde(rename(deserialize = "camera"))]
   pub camera_config: Option<CameraConfig>,
+  // 省略した場合は白から水色への空のグラデーション
+  #[serde(default)]
+  pub background: Option<BackgroundConfig>,
+  // 形状を持たない光源
+  #[serde(default)]
+  pub lights: Vec<LightConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum LightConfig {
+  #[serde(rename(serialize = "point", deserialize = "point"))]
+  Point {
+    position: (f64, f64, f64),
+    color: (f64, f64, f64),
+    #[serde(default = "default_intensity")]
+    intensity: f64,
+  },
+  // 角度は円錐の中心軸からの角度(度)。inner_angle の内側は最も明るく、angle の外側は照らさない
+  #[serde(rename(serialize = "spot", deserialize = "spot"))]
+  Spot {
+    position: (f64, f64, f64),
+    direction: (f64, f64, f64),
+    color: (f64, f64, f64),
+    #[serde(default = "default_intensity")]
+    intensity: f64,
+    angle: f64,
+    #[serde(default)]
+    inner_angle: Option<f64>,
+  },
+  // direction は光が進む向き
+  #[serde(rename(serialize = "directional", deserialize = "directional"))]
+  Directional {
+    direction: (f64, f64, f64),
+    color: (f64, f64, f64),
+    #[serde(default = "default_intensity")]
+    intensity: f64,
+  },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum BackgroundConfig {
+  #[serde(rename(serialize = "solid", deserialize = "solid"))]
+  Solid { color: (f64, f64, f64) },
+  #[serde(rename(serialize = "gradient", deserialize = "gradient"))]
+  Gradient {
+    bottom: (f64, f64, f64),
+    top: (f64, f64, f64),
+    #[serde(default = "default_up")]
+    up: (f64, f64, f64),
+  },
+  #[serde(rename(serialize = "black", deserialize = "black"))]
+  Black,
+  #[serde(rename(serialize = "environment_map", deserialize = "environment_map"))]
+  EnvironmentMap {
+    // .hdr または .exr の正距円筒図法の画像
+    path: String,
+    // Z軸まわりの回転(度)
+    #[serde(default)]
+    rotation: f64,
+    #[serde(default = "default_intensity")]
+    intensity: f64,
+  },
+}
+
+fn default_up() -> (f64, f64, f64) {
+  (0.0, 0.0, 1.0)
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -33,6 +112,9 @@ pub enum ObjectConfig {
     radius: f64,
     name: String,
     material: MaterialConfig,
+    // 物体全体に掛ける座標変換
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transform: Option<TransformConfig>,
   },
   #[serde(rename(serialize = "box", deserialize = "box"))]
   Box {
@@ -40,17 +122,278 @@ pub enum ObjectConfig {
     right: (f64, f64, f64),
     name: String,
     material: MaterialConfig,
+    // 物体全体に掛ける座標変換
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transform: Option<TransformConfig>,
   },
+  #[serde(rename(serialize = "triangle", deserialize = "triangle"))]
+  Triangle {
+    vertices: [(f64, f64, f64); 3],
+    // 頂点ごとの法線(省略すると面の法線)
+    #[serde(default)]
+    normals: Option<[(f64, f64, f64); 3]>,
+    name: String,
+    material: MaterialConfig,
+    // 物体全体に掛ける座標変換
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transform: Option<TransformConfig>,
+  },
+  #[serde(rename(serialize = "mesh", deserialize = "mesh"))]
+  Mesh {
+    vertices: Vec<(f64, f64, f64)>,
+    // 頂点ごとの法線(省略すると面の法線)
+    #[serde(default)]
+    normals: Option<Vec<(f64, f64, f64)>>,
+    // 各面を構成する頂点の添字。反時計回りに見える側が表
+    faces: Vec<[usize; 3]>,
+    name: String,
+    material: MaterialConfig,
+    // 物体全体に掛ける座標変換
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transform: Option<TransformConfig>,
+  },
+  #[serde(rename(serialize = "obj", deserialize = "obj"))]
+  Obj {
+    // Wavefront OBJ ファイル
+    path: String,
+    // OBJ の mtllib の代わりに使う MTL ファイル
+    #[serde(default)]
+    mtl: Option<String>,
+    name: String,
+    // MTL で材質が決まらない面に使う材質
+    #[serde(default = "default_obj_material")]
+    material: MaterialConfig,
+    // 頂点に焼き込む変換。拡大縮小、X軸、Y軸、Z軸の順の回転(度)、平行移動の順に適用する
+    // transform と同じ行列で計算し、transform はこれを適用した後にさらに掛ける
+    #[serde(default)]
+    translate: (f64, f64, f64),
+    #[serde(default)]
+    rotate: (f64, f64, f64),
+    #[serde(default = "default_scale")]
+    scale: (f64, f64, f64),
+    // 物体全体に掛ける座標変換
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transform: Option<TransformConfig>,
+  },
+}
+
+// 拡大縮小、回転(並べた順)、平行移動の順に適用する
+// matrix を指定した場合は、それらの後にさらに掛ける
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransformConfig {
+  #[serde(default = "default_scale")]
+  pub scale: (f64, f64, f64),
+  #[serde(default)]
+  pub rotate: Vec<RotationConfig>,
+  #[serde(default)]
+  pub translate: (f64, f64, f64),
+  // 行優先の 4x4 行列。アフィン変換に限る(最後の行は [0, 0, 0, 1])
+  #[serde(default)]
+  pub matrix: Option<[[f64; 4]; 4]>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct RotationConfig {
+  pub axis: (f64, f64, f64),
+  // 回転角(度)
+  pub angle: f64,
+}
+
+impl TransformConfig {
+  pub fn to_matrix(&self) -> Mat4 {
+    let mut m = Mat4::scale(&Vec3::from_tuple(self.scale));
+    for r in self.rotate.iter() {
+      m = Mat4::rotation(&Vec3::from_tuple(r.axis), r.angle.to_radians()) * m;
+    }
+    m = Mat4::translation(&Vec3::from_tuple(self.translate)) * m;
+    match self.matrix {
+      Some(matrix) => Mat4::new(matrix) * m,
+      None => m,
+    }
+  }
+}
+
+// 逆行列がない(拡大率が 0 や NaN を含むなど)変換と、アフィンでない(射影を含む)変換は、
+// どの物体のどの設定かを示して止める
+// Transformed は物体座標の t をそのままワールド座標で使うので、アフィン変換しか扱えない
+fn checked_transform(matrix: Mat4, name: &str, key: &str) -> Mat4 {
+  let bottom = [0.0, 0.0, 0.0, 1.0];
+  if (0..4).any(|j| (matrix.get(3, j) - bottom[j]).abs() > 1.0e-9) {
+    panic!(
+      "object {}: {} must be affine (the bottom row of matrix must be [0, 0, 0, 1])",
+      name, key
+    );
+  }
+  if matrix.inverse().is_none() {
+    panic!(
+      "object {}: {} is not invertible (zero scale or non-finite value)",
+      name, key
+    );
+  }
+  matrix
+}
+
+// 向きとして使うベクトルは、長さ 0 や NaN を含むと正規化できないので、どの設定かを示して止める
+fn checked_direction(v: (f64, f64, f64), owner: &str, key: &str) -> Vec3 {
+  let v = Vec3::from_tuple(v);
+  let norm = v.norm();
+  if !norm.is_finite() || norm == 0.0 {
+    panic!("{}: {} must be a non-zero finite vector", owner, key);
+  }
+  v
+}
+
+fn default_obj_material() -> MaterialConfig {
+  MaterialConfig::Lambertian {
+    albedo: TextureConfig::Color((0.7, 0.7, 0.7)),
+  }
+}
+
+fn default_scale() -> (f64, f64, f64) {
+  (1.0, 1.0, 1.0)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum MaterialConfig {
   #[serde(rename(serialize = "lambertian", deserialize = "lambertian"))]
-  Lambertian { albedo: (f64, f64, f64) },
+  Lambertian { albedo: TextureConfig },
   #[serde(rename(serialize = "metal", deserialize = "metal"))]
-  Metal { albedo: (f64, f64, f64), fuzzy: f64 },
+  Metal { albedo: TextureConfig, fuzzy: f64 },
   #[serde(rename(serialize = "dielectric", deserialize = "dielectric"))]
-  Dielectric { refraction_index: f64 },
+  Dielectric {
+    refraction_index: f64,
+    // 内部を単位長さ進むごとの吸収係数(Beer–Lambert)。省略すると無色透明
+    #[serde(default)]
+    absorption: (f64, f64, f64),
+  },
+  // GGX マイクロファセットによる金属/粗さモデル
+  #[serde(rename(serialize = "microfacet", deserialize = "microfacet"))]
+  Microfacet {
+    base_color: TextureConfig,
+    #[serde(default)]
+    metallic: f64,
+    #[serde(default = "default_roughness")]
+    roughness: f64,
+    // 非金属の反射の強さ。0.5 で垂直入射の反射率が 4% になる
+    #[serde(default = "default_specular")]
+    specular: f64,
+  },
+  #[serde(rename(serialize = "diffuse_light", deserialize = "diffuse_light"))]
+  DiffuseLight {
+    color: (f64, f64, f64),
+    #[serde(default = "default_intensity")]
+    intensity: f64,
+  },
+}
+
+fn default_intensity() -> f64 {
+  1.0
+}
+
+fn default_roughness() -> f64 {
+  0.5
+}
+
+fn default_specular() -> f64 {
+  0.5
+}
+
+// 色をそのまま書けば一様な色、checker や image を書けば模様になる
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum TextureConfig {
+  Color((f64, f64, f64)),
+  Pattern(PatternConfig),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum PatternConfig {
+  #[serde(rename(serialize = "checker", deserialize = "checker"))]
+  Checker {
+    even: Box<TextureConfig>,
+    odd: Box<TextureConfig>,
+    #[serde(default = "default_checker_scale")]
+    scale: f64,
+  },
+  // PNG や JPEG などの画像を UV 座標で貼る
+  #[serde(rename(serialize = "image", deserialize = "image"))]
+  Image { path: String },
+  // 以下は衝突位置から計算するノイズ模様。ramp を省略すると模様ごとの既定の色になる
+  #[serde(rename(serialize = "noise", deserialize = "noise"))]
+  Noise {
+    #[serde(default = "default_noise_scale")]
+    scale: f64,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    ramp: Option<Vec<ColorStopConfig>>,
+  },
+  #[serde(rename(serialize = "turbulence", deserialize = "turbulence"))]
+  Turbulence {
+    #[serde(default = "default_noise_scale")]
+    scale: f64,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default = "default_octaves")]
+    octaves: usize,
+    #[serde(default)]
+    ramp: Option<Vec<ColorStopConfig>>,
+  },
+  #[serde(rename(serialize = "marble", deserialize = "marble"))]
+  Marble {
+    #[serde(default = "default_noise_scale")]
+    scale: f64,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default = "default_octaves")]
+    octaves: usize,
+    // 縞をゆがめる強さ
+    #[serde(default = "default_marble_turbulence")]
+    turbulence: f64,
+    #[serde(default)]
+    ramp: Option<Vec<ColorStopConfig>>,
+  },
+  #[serde(rename(serialize = "wood", deserialize = "wood"))]
+  Wood {
+    #[serde(default = "default_noise_scale")]
+    scale: f64,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default = "default_octaves")]
+    octaves: usize,
+    // 年輪をゆがめる強さ
+    #[serde(default = "default_wood_turbulence")]
+    turbulence: f64,
+    #[serde(default)]
+    ramp: Option<Vec<ColorStopConfig>>,
+  },
+}
+
+// ノイズの値 position(0 から 1)での色
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct ColorStopConfig {
+  pub position: f64,
+  pub color: (f64, f64, f64),
+}
+
+fn default_checker_scale() -> f64 {
+  1.0
+}
+
+fn default_noise_scale() -> f64 {
+  1.0
+}
+
+fn default_octaves() -> usize {
+  7
+}
+
+fn default_marble_turbulence() -> f64 {
+  10.0
+}
+
+fn default_wood_turbulence() -> f64 {
+  0.5
 }
 
 impl CameraConfig {
@@ -70,64 +413,168 @@ impl CameraConfig {
 
 impl ObjectConfig {
   pub fn to_object(&self) -> Box<dyn Shape> {
+    let shape = self.to_shape();
+    match self.transform() {
+      Some(transform) => {
+        let matrix = checked_transform(transform.to_matrix(), shape.name(), "transform");
+        Box::new(Transformed::new(shape, matrix))
+      }
+      None => shape,
+    }
+  }
+
+  fn transform(&self) -> Option<&TransformConfig> {
+    match self {
+      ObjectConfig::Sphere { transform, .. }
+      | ObjectConfig::Box { transform, .. }
+      | ObjectConfig::Triangle { transform, .. }
+      | ObjectConfig::Mesh { transform, .. }
+      | ObjectConfig::Obj { transform, .. } => transform.as_ref(),
+    }
+  }
+
+  // 座標変換を掛ける前の物体
+  fn to_shape(&self) -> Box<dyn Shape> {
     match self {
       ObjectConfig::Sphere {
         center,
         radius,
         name,
         material,
+        ..
       } => Box::new(Sphere::new(
         Vec3::from_tuple(*center),
         *radius,
         name.to_string(),
         material.to_material(),
       )),
-      _ => {
-        unimplemented!()
+      ObjectConfig::Box {
+        left,
+        right,
+        name,
+        material,
+        ..
+      } => Box::new(Cuboid::new(
+        Vec3::from_tuple(*left),
+        Vec3::from_tuple(*right),
+        name.to_string(),
+        material.to_material(),
+      )),
+      ObjectConfig::Triangle {
+        vertices,
+        normals,
+        name,
+        material,
+        ..
+      } => Box::new(Triangle::new(
+        vertices.map(Vec3::from_tuple),
+        normals.map(|n| n.map(Vec3::from_tuple)),
+        name.to_string(),
+        material.to_material(),
+      )),
+      ObjectConfig::Mesh {
+        vertices,
+        normals,
+        faces,
+        name,
+        material,
+        ..
+      } => Box::new(Mesh::new(
+        name.to_string(),
+        vertices.iter().map(|v| Vec3::from_tuple(*v)).collect(),
+        normals
+          .as_ref()
+          .map(|n| n.iter().map(|v| Vec3::from_tuple(*v)).collect()),
+        faces,
+        material.to_material(),
+      )),
+      ObjectConfig::Obj {
+        path,
+        mtl,
+        name,
+        material,
+        translate,
+        rotate,
+        scale,
+        ..
+      } => {
+        let (rx, ry, rz) = *rotate;
+        let transform = TransformConfig {
+          scale: *scale,
+          rotate: [
+            ((1.0, 0.0, 0.0), rx),
+            ((0.0, 1.0, 0.0), ry),
+            ((0.0, 0.0, 1.0), rz),
+          ]
+          .iter()
+          .map(|(axis, angle)| RotationConfig {
+            axis: *axis,
+            angle: *angle,
+          })
+          .collect(),
+          translate: *translate,
+          matrix: None,
+        }
+        .to_matrix();
+        let transform = checked_transform(transform, name, "translate/rotate/scale");
+        let (mesh, warnings) = load_obj(
+          Path::new(path),
+          mtl.as_ref().map(Path::new),
+          name.to_string(),
+          &transform,
+          material.to_material(),
+        )
+        .unwrap_or_else(|e| panic!("failed to load {}: {}", path, e));
+        for warning in warnings.iter() {
+          eprintln!("warning: {}: {}", path, warning);
+        }
+        Box::new(mesh)
       }
     }
   }
 
-  pub fn gen_random() -> Vec<ObjectConfig> {
+  pub fn gen_random(rng: &mut RandGen) -> Vec<ObjectConfig> {
     let mut ret = vec![];
     for a in -11..11 {
       for b in -11..11 {
-        let choose_material = get_uniform_random();
+        let choose_material = get_uniform_random(rng);
         let radius = 0.3;
         let center = Vec3::new(
-          (a as f64) * 1.5 + 0.9 * get_uniform_random(),
-          (b as f64) * 1.5 + 0.9 * get_uniform_random(),
+          (a as f64) * 1.5 + 0.9 * get_uniform_random(rng),
+          (b as f64) * 1.5 + 0.9 * get_uniform_random(rng),
           radius / 2.0,
         );
         let name = format!("sphere {}{}", a, b);
         if (center - Vec3::new(4.0, 0.0, radius / 2.0)).norm() > 0.9 {
           if choose_material < 0.33 {
             let albedo = Vec3::new(
-              get_random_in_range(0.0, 0.99),
-              get_random_in_range(0.0, 0.99),
-              get_random_in_range(0.0, 0.99),
+              get_random_in_range(rng, 0.0, 0.99),
+              get_random_in_range(rng, 0.0, 0.99),
+              get_random_in_range(rng, 0.0, 0.99),
             );
             ret.push(ObjectConfig::Sphere {
               center: center.to_tuple(),
               radius: radius,
               name: name,
+              transform: None,
               material: MaterialConfig::Lambertian {
-                albedo: albedo.to_tuple(),
+                albedo: TextureConfig::Color(albedo.to_tuple()),
               },
             });
           } else if choose_material < 0.666 {
             let albedo = Vec3::new(
-              get_random_in_range(0.5, 0.99),
-              get_random_in_range(0.5, 0.99),
-              get_random_in_range(0.5, 0.99),
+              get_random_in_range(rng, 0.5, 0.99),
+              get_random_in_range(rng, 0.5, 0.99),
+              get_random_in_range(rng, 0.5, 0.99),
             );
-            let fuzzy = get_random_in_range(0.0, 0.5);
+            let fuzzy = get_random_in_range(rng, 0.0, 0.5);
             ret.push(ObjectConfig::Sphere {
               center: center.to_tuple(),
               radius: radius,
               name: name,
+              transform: None,
               material: MaterialConfig::Metal {
-                albedo: albedo.to_tuple(),
+                albedo: TextureConfig::Color(albedo.to_tuple()),
                 fuzzy: fuzzy,
               },
             });
@@ -136,8 +583,10 @@ impl ObjectConfig {
               center: center.to_tuple(),
               radius: radius,
               name: name,
+              transform: None,
               material: MaterialConfig::Dielectric {
                 refraction_index: 1.5,
+                absorption: (0.0, 0.0, 0.0),
               },
             });
           }
@@ -152,14 +601,211 @@ impl MaterialConfig {
   pub fn to_material(&self) -> Material {
     match self {
       MaterialConfig::Lambertian { albedo } => Material::Lambertian {
-        albedo: Vec3::new(albedo.0, albedo.1, albedo.2),
+        albedo: albedo.to_texture(),
       },
       MaterialConfig::Metal { albedo, fuzzy } => Material::Metal {
-        albedo: Vec3::new(albedo.0, albedo.1, albedo.2),
+        albedo: albedo.to_texture(),
         fuzzy: *fuzzy,
       },
-      MaterialConfig::Dielectric { refraction_index } => Material::Dielectric {
+      MaterialConfig::Dielectric {
+        refraction_index,
+        absorption,
+      } => Material::Dielectric {
         refraction_index: *refraction_index,
+        absorption: Vec3::from_tuple(*absorption),
+      },
+      MaterialConfig::Microfacet {
+        base_color,
+        metallic,
+        roughness,
+        specular,
+      } => Material::Microfacet {
+        base_color: base_color.to_texture(),
+        metallic: *metallic,
+        roughness: *roughness,
+        specular: *specular,
+      },
+      MaterialConfig::DiffuseLight { color, intensity } => Material::DiffuseLight {
+        emit: Vec3::from_tuple(*color) * *intensity,
+      },
+    }
+  }
+}
+
+impl TextureConfig {
+  pub fn to_texture(&self) -> Texture {
+    match self {
+      TextureConfig::Color(color) => Texture::Constant(Vec3::from_tuple(*color)),
+      TextureConfig::Pattern(PatternConfig::Checker { even, odd, scale }) => Texture::Checker {
+        even: Box::new(even.to_texture()),
+        odd: Box::new(odd.to_texture()),
+        scale: *scale,
+      },
+      TextureConfig::Pattern(PatternConfig::Image { path }) => Texture::Image(Arc::new(
+        ImageTexture::load(Path::new(path))
+          .unwrap_or_else(|e| panic!("failed to load texture {}: {}", path, e)),
+      )),
+      TextureConfig::Pattern(PatternConfig::Noise { scale, seed, ramp }) => Self::noise_texture(
+        NoisePattern::Perlin,
+        *scale,
+        *seed,
+        ramp,
+        ColorRamp::grayscale(),
+      ),
+      TextureConfig::Pattern(PatternConfig::Turbulence {
+        scale,
+        seed,
+        octaves,
+        ramp,
+      }) => Self::noise_texture(
+        NoisePattern::Turbulence { octaves: *octaves },
+        *scale,
+        *seed,
+        ramp,
+        ColorRamp::grayscale(),
+      ),
+      TextureConfig::Pattern(PatternConfig::Marble {
+        scale,
+        seed,
+        octaves,
+        turbulence,
+        ramp,
+      }) => Self::noise_texture(
+        NoisePattern::Marble {
+          octaves: *octaves,
+          turbulence: *turbulence,
+        },
+        *scale,
+        *seed,
+        ramp,
+        ColorRamp::new(vec![
+          (0.0, Vec3::new(0.15, 0.15, 0.17)),
+          (1.0, Vec3::new(0.9, 0.9, 0.88)),
+        ]),
+      ),
+      TextureConfig::Pattern(PatternConfig::Wood {
+        scale,
+        seed,
+        octaves,
+        turbulence,
+        ramp,
+      }) => Self::noise_texture(
+        NoisePattern::Wood {
+          octaves: *octaves,
+          turbulence: *turbulence,
+        },
+        *scale,
+        *seed,
+        ramp,
+        ColorRamp::new(vec![
+          (0.0, Vec3::new(0.45, 0.27, 0.12)),
+          (0.7, Vec3::new(0.6, 0.4, 0.2)),
+          (1.0, Vec3::new(0.3, 0.17, 0.07)),
+        ]),
+      ),
+    }
+  }
+
+  fn noise_texture(
+    pattern: NoisePattern,
+    scale: f64,
+    seed: u64,
+    ramp: &Option<Vec<ColorStopConfig>>,
+    default_ramp: ColorRamp,
+  ) -> Texture {
+    let ramp = match ramp {
+      Some(stops) => ColorRamp::new(
+        stops
+          .iter()
+          .map(|s| (s.position, Vec3::from_tuple(s.color)))
+          .collect(),
+      ),
+      None => default_ramp,
+    };
+    Texture::Noise {
+      perlin: Arc::new(Perlin::new(seed)),
+      pattern,
+      scale,
+      ramp,
+    }
+  }
+}
+
+impl LightConfig {
+  pub fn to_light(&self) -> Light {
+    match self {
+      LightConfig::Point {
+        position,
+        color,
+        intensity,
+      } => Light::Point {
+        position: Vec3::from_tuple(*position),
+        intensity: Vec3::from_tuple(*color) * *intensity,
+      },
+      LightConfig::Spot {
+        position,
+        direction,
+        color,
+        intensity,
+        angle,
+        inner_angle,
+      } => {
+        // 円錐は中心軸から 0 度より広く 180 度まで。inner_angle は 0 度から angle まで
+        if angle.is_nan() || *angle <= 0.0 || *angle > 180.0 {
+          panic!(
+            "spot light: angle must be in (0, 180] degrees, got {}",
+            angle
+          );
+        }
+        // 省略した場合は angle の 8 割までを最も明るくする
+        let inner_angle = inner_angle.unwrap_or(angle * 0.8);
+        if !(0.0..=*angle).contains(&inner_angle) {
+          panic!(
+            "spot light: inner_angle must be in [0, angle] = [0, {}] degrees, got {}",
+            angle, inner_angle
+          );
+        }
+        Light::Spot {
+          position: Vec3::from_tuple(*position),
+          direction: checked_direction(*direction, "spot light", "direction"),
+          intensity: Vec3::from_tuple(*color) * *intensity,
+          inner_angle: inner_angle.to_radians(),
+          outer_angle: angle.to_radians(),
+        }
+      }
+      LightConfig::Directional {
+        direction,
+        color,
+        intensity,
+      } => Light::Directional {
+        direction: checked_direction(*direction, "directional light", "direction"),
+        irradiance: Vec3::from_tuple(*color) * *intensity,
+      },
+    }
+  }
+}
+
+impl BackgroundConfig {
+  pub fn to_background(&self) -> Background {
+    match self {
+      BackgroundConfig::Solid { color } => Background::Solid {
+        color: Vec3::from_tuple(*color),
+      },
+      BackgroundConfig::Gradient { bottom, top, up } => Background::Gradient {
+        bottom: Vec3::from_tuple(*bottom),
+        top: Vec3::from_tuple(*top),
+        up: checked_direction(*up, "background gradient", "up"),
+      },
+      BackgroundConfig::Black => Background::Black,
+      BackgroundConfig::EnvironmentMap {
+        path,
+        rotation,
+        intensity,
+      } => Background::EnvironmentMap {
+        map: Arc::new(
+          EnvironmentMap::load(Path::new(path), *rotation, *intensity)
+            .unwrap_or_else(|e| panic!("failed to load environment map {}: {}", path, e)),
+        ),
       },
     }
   }
@@ -168,13 +814,318 @@ impl MaterialConfig {
 #[cfg(test)]
 mod test {
   use super::*;
+  use crate::base::math::new_rand_gen;
   use serde_yaml;
   #[test]
   fn test_object_gen_random() {
-    let objects = ObjectConfig::gen_random();
+    let objects = ObjectConfig::gen_random(&mut new_rand_gen(0));
     let str = serde_yaml::to_string(&objects);
     assert_eq!(objects.len() > 0, true);
     assert_eq!(str.is_ok(), true);
     println!("{}", str.unwrap());
   }
+
+  #[test]
+  fn test_background_config() {
+    let black: BackgroundConfig = serde_yaml::from_str("black").unwrap();
+    assert!(matches!(black.to_background(), Background::Black));
+
+    let gradient: BackgroundConfig =
+      serde_yaml::from_str("gradient:\n  bottom: [1.0, 1.0, 1.0]\n  top: [0.0, 0.0, 0.0]").unwrap();
+    match gradient.to_background() {
+      Background::Gradient { up, .. } => assert_eq!(up, Vec3::new(0.0, 0.0, 1.0)),
+      _ => panic!("expected gradient"),
+    }
+
+    let solid: BackgroundConfig = serde_yaml::from_str("solid:\n  color: [0.1, 0.2, 0.3]").unwrap();
+    match solid.to_background() {
+      Background::Solid { color } => assert_eq!(color, Vec3::new(0.1, 0.2, 0.3)),
+      _ => panic!("expected solid"),
+    }
+  }
+
+  #[test]
+  #[should_panic(expected = "background gradient: up must be a non-zero finite vector")]
+  fn test_zero_gradient_up() {
+    let gradient: BackgroundConfig = serde_yaml::from_str(
+      "gradient:\n  bottom: [1.0, 1.0, 1.0]\n  top: [0.0, 0.0, 0.0]\n  up: [0.0, 0.0, 0.0]",
+    )
+    .unwrap();
+    gradient.to_background();
+  }
+
+  #[test]
+  #[should_panic(expected = "background gradient: up must be a non-zero finite vector")]
+  fn test_non_finite_gradient_up() {
+    let gradient: BackgroundConfig = serde_yaml::from_str(
+      "gradient:\n  bottom: [1.0, 1.0, 1.0]\n  top: [0.0, 0.0, 0.0]\n  up: [0.0, .nan, 1.0]",
+    )
+    .unwrap();
+    gradient.to_background();
+  }
+
+  #[test]
+  fn test_light_config() {
+    let yaml = "- point:\n    position: [0.0, 0.0, 5.0]\n    color: [1.0, 0.5, 0.5]\n    intensity: 10.0\n\
+                - spot:\n    position: [0.0, 0.0, 5.0]\n    direction: [0.0, 0.0, -1.0]\n    color: [1.0, 1.0, 1.0]\n    angle: 30.0\n\
+                - directional:\n    direction: [1.0, 0.0, -1.0]\n    color: [1.0, 1.0, 1.0]";
+    let configs: Vec<LightConfig> = serde_yaml::from_str(yaml).unwrap();
+    let lights: Vec<Light> = configs.iter().map(|c| c.to_light()).collect();
+    match &lights[0] {
+      Light::Point { intensity, .. } => assert_eq!(*intensity, Vec3::new(10.0, 5.0, 5.0)),
+      _ => panic!("expected point light"),
+    }
+    match &lights[1] {
+      Light::Spot {
+        inner_angle,
+        outer_angle,
+        ..
+      } => {
+        assert!((outer_angle - 30f64.to_radians()).abs() < 1e-12);
+        assert!((inner_angle - 24f64.to_radians()).abs() < 1e-12);
+      }
+      _ => panic!("expected spot light"),
+    }
+    assert!(matches!(lights[2], Light::Directional { .. }));
+  }
+
+  #[test]
+  #[should_panic(expected = "spot light: direction must be a non-zero finite vector")]
+  fn test_zero_spot_direction() {
+    let yaml = "spot:\n  position: [0.0, 0.0, 5.0]\n  direction: [0.0, 0.0, 0.0]\n  color: [1.0, 1.0, 1.0]\n  angle: 30.0";
+    let config: LightConfig = serde_yaml::from_str(yaml).unwrap();
+    config.to_light();
+  }
+
+  #[test]
+  #[should_panic(expected = "spot light: angle must be in (0, 180] degrees")]
+  fn test_non_finite_spot_angle() {
+    let yaml = "spot:\n  position: [0.0, 0.0, 5.0]\n  direction: [0.0, 0.0, -1.0]\n  color: [1.0, 1.0, 1.0]\n  angle: .nan";
+    let config: LightConfig = serde_yaml::from_str(yaml).unwrap();
+    config.to_light();
+  }
+
+  #[test]
+  #[should_panic(expected = "spot light: inner_angle must be in [0, angle]")]
+  fn test_spot_inner_angle_out_of_range() {
+    let yaml = "spot:\n  position: [0.0, 0.0, 5.0]\n  direction: [0.0, 0.0, -1.0]\n  color: [1.0, 1.0, 1.0]\n  angle: 30.0\n  inner_angle: 40.0";
+    let config: LightConfig = serde_yaml::from_str(yaml).unwrap();
+    config.to_light();
+  }
+
+  #[test]
+  #[should_panic(expected = "directional light: direction must be a non-zero finite vector")]
+  fn test_non_finite_directional_direction() {
+    let yaml = "directional:\n  direction: [0.0, .inf, -1.0]\n  color: [1.0, 1.0, 1.0]";
+    let config: LightConfig = serde_yaml::from_str(yaml).unwrap();
+    config.to_light();
+  }
+
+  #[test]
+  fn test_microfacet_config() {
+    let yaml = "microfacet:\n  base_color: [1.0, 0.8, 0.3]\n  metallic: 1.0";
+    let config: MaterialConfig = serde_yaml::from_str(yaml).unwrap();
+    match config.to_material() {
+      Material::Microfacet {
+        metallic,
+        roughness,
+        specular,
+        ..
+      } => {
+        assert_eq!(metallic, 1.0);
+        assert_eq!(roughness, 0.5);
+        assert_eq!(specular, 0.5);
+      }
+      _ => panic!("expected microfacet"),
+    }
+  }
+
+  #[test]
+  fn test_texture_config() {
+    let color: MaterialConfig =
+      serde_yaml::from_str("lambertian:\n  albedo: [0.1, 0.2, 0.3]").unwrap();
+    match color.to_material() {
+      Material::Lambertian {
+        albedo: Texture::Constant(c),
+      } => assert_eq!(c, Vec3::new(0.1, 0.2, 0.3)),
+      _ => panic!("expected constant lambertian"),
+    }
+
+    let yaml = "metal:
+  fuzzy: 0.0
+  albedo:
+    checker:
+      even: [1.0, 1.0, 1.0]
+      odd:
+        checker:
+          even: [0.0, 0.0, 0.0]
+          odd: [0.5, 0.5, 0.5]
+          scale: 0.5";
+    let checker: MaterialConfig = serde_yaml::from_str(yaml).unwrap();
+    match checker.to_material() {
+      Material::Metal {
+        albedo: Texture::Checker { odd, scale, .. },
+        ..
+      } => {
+        assert_eq!(scale, 1.0);
+        assert!(matches!(*odd, Texture::Checker { scale, .. } if scale == 0.5));
+      }
+      _ => panic!("expected checker metal"),
+    }
+
+    let image: TextureConfig = serde_yaml::from_str("image:\n  path: earth.png").unwrap();
+    assert!(matches!(
+      image,
+      TextureConfig::Pattern(PatternConfig::Image { .. })
+    ));
+  }
+
+  #[test]
+  fn test_noise_texture_config() {
+    let yaml = "marble:
+  scale: 4.0
+  ramp:
+    - position: 0.0
+      color: [1.0, 0.0, 0.0]
+    - position: 1.0
+      color: [1.0, 0.0, 0.0]";
+    let marble: TextureConfig = serde_yaml::from_str(yaml).unwrap();
+    match marble.to_texture() {
+      Texture::Noise {
+        pattern: NoisePattern::Marble {
+          octaves,
+          turbulence,
+        },
+        scale,
+        ..
+      } => {
+        assert_eq!(octaves, 7);
+        assert_eq!(turbulence, 10.0);
+        assert_eq!(scale, 4.0);
+      }
+      _ => panic!("expected marble"),
+    }
+    // 色が1色しかない ramp なので、どこでも同じ色になる
+    let p = Vec3::new(0.3, 0.2, 0.1);
+    assert_eq!(
+      marble.to_texture().value(0.0, 0.0, &p),
+      Vec3::new(1.0, 0.0, 0.0)
+    );
+
+    // 同じシードなら同じ模様になる
+    for name in ["noise", "turbulence", "wood"] {
+      let yaml = format!("{}:\n  seed: 3", name);
+      let a: TextureConfig = serde_yaml::from_str(&yaml).unwrap();
+      let b: TextureConfig = serde_yaml::from_str(&yaml).unwrap();
+      assert_eq!(
+        a.to_texture().value(0.0, 0.0, &p),
+        b.to_texture().value(0.0, 0.0, &p)
+      );
+    }
+  }
+
+  #[test]
+  fn test_obj_config() {
+    let yaml =
+      "obj:\n  name: cube\n  path: example_yaml/models/cube.obj\n  translate: [0.0, 0.0, 5.0]";
+    let config: ObjectConfig = serde_yaml::from_str(yaml).unwrap();
+    let object = config.to_object();
+    assert_eq!(object.name(), "cube");
+    let bbox = object.bounding_box();
+    assert!((bbox.min().get_z() - 4.5).abs() < 1e-3);
+    assert!((bbox.max().get_z() - 5.5).abs() < 1e-3);
+  }
+
+  #[test]
+  #[should_panic(expected = "object box: transform is not invertible")]
+  fn test_singular_transform() {
+    let yaml = "box:
+  name: box
+  left: [-1.0, -1.0, -1.0]
+  right: [1.0, 1.0, 1.0]
+  material:
+    lambertian:
+      albedo: [0.5, 0.5, 0.5]
+  transform:
+    scale: [1.0, 0.0, 1.0]";
+    let config: ObjectConfig = serde_yaml::from_str(yaml).unwrap();
+    config.to_object();
+  }
+
+  #[test]
+  #[should_panic(expected = "object box: transform must be affine")]
+  fn test_projective_transform() {
+    let yaml = "box:
+  name: box
+  left: [-1.0, -1.0, -1.0]
+  right: [1.0, 1.0, 1.0]
+  material:
+    lambertian:
+      albedo: [0.5, 0.5, 0.5]
+  transform:
+    matrix: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.5, 1.0]]";
+    let config: ObjectConfig = serde_yaml::from_str(yaml).unwrap();
+    config.to_object();
+  }
+
+  #[test]
+  #[should_panic(expected = "object cube: translate/rotate/scale is not invertible")]
+  fn test_singular_obj_transform() {
+    let yaml = "obj:\n  name: cube\n  path: example_yaml/models/cube.obj\n  scale: [0.0, 1.0, 1.0]";
+    let config: ObjectConfig = serde_yaml::from_str(yaml).unwrap();
+    config.to_object();
+  }
+
+  #[test]
+  fn test_obj_transform_order() {
+    // 頂点に焼き込む scale、rotate、translate を先に適用し、その後に transform を掛ける
+    let yaml = "obj:
+  name: cube
+  path: example_yaml/models/cube.obj
+  scale: [2.0, 1.0, 1.0]
+  rotate: [0.0, 0.0, 90.0]
+  translate: [0.0, 0.0, 5.0]
+  transform:
+    scale: [1.0, 1.0, 2.0]";
+    let config: ObjectConfig = serde_yaml::from_str(yaml).unwrap();
+    let bbox = config.to_object().bounding_box();
+    // X方向に2倍した後にZ軸まわりに90度回すので、Y方向に長くなる
+    assert!((bbox.max().get_x() - 0.5).abs() < 1e-3);
+    assert!((bbox.max().get_y() - 1.0).abs() < 1e-3);
+    // z = 5 に移してから2倍するので、9 から 11 になる
+    assert!((bbox.min().get_z() - 9.0).abs() < 1e-3);
+    assert!((bbox.max().get_z() - 11.0).abs() < 1e-3);
+  }
+
+  #[test]
+  fn test_transform_config() {
+    let yaml = "box:
+  name: box
+  left: [-1.0, -1.0, -1.0]
+  right: [1.0, 1.0, 1.0]
+  material:
+    lambertian:
+      albedo: [0.5, 0.5, 0.5]
+  transform:
+    scale: [2.0, 1.0, 1.0]
+    rotate:
+      - axis: [0.0, 0.0, 1.0]
+        angle: 90.0
+    translate: [0.0, 0.0, 10.0]";
+    let config: ObjectConfig = serde_yaml::from_str(yaml).unwrap();
+    let bbox = config.to_object().bounding_box();
+    // X方向に2倍した後にZ軸まわりに90度回すので、Y方向に長くなる
+    assert!((bbox.max().get_x() - 1.0).abs() < 1e-9);
+    assert!((bbox.max().get_y() - 2.0).abs() < 1e-9);
+    assert!((bbox.min().get_z() - 9.0).abs() < 1e-9);
+
+    let matrix: TransformConfig = serde_yaml::from_str(
+      "matrix: [[1.0, 0.0, 0.0, 3.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]]\ntranslate: [1.0, 0.0, 0.0]",
+    )
+    .unwrap();
+    assert_eq!(
+      matrix.to_matrix().transform_point(&Vec3::zero_vector()),
+      Vec3::new(4.0, 0.0, 0.0)
+    );
+  }
 }
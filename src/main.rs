@@ -1,25 +1,88 @@
-use std::borrow::Borrow;
 use std::path::Path;
 
 use ray_tracers::config::config::Config;
+use ray_tracers::scene::output::save_film;
 use ray_tracers::scene::Scene;
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 {
-        println!("Usage: {} <config.yaml>", args[0]);
-        return;
+    let mut config_path = None;
+    let mut seed = None;
+    let mut sample_map = None;
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--seed" => {
+                seed = Some(
+                    iter.next()
+                        .and_then(|s| s.parse::<u64>().ok())
+                        .expect("--seed requires an unsigned integer"),
+                );
+            }
+            "--sample-map" => {
+                sample_map = Some(
+                    iter.next()
+                        .expect("--sample-map requires an output path")
+                        .clone(),
+                );
+            }
+            _ => config_path = Some(arg),
+        }
     }
-    let config = Config::from_yaml(Path::new(args[1].as_str())).unwrap();
-    let scene = Scene::build_from_config(&config);
-    let buf = scene.render();
-
-    image::save_buffer(
-        &Path::new(config.output_name()),
-        buf.borrow(),
-        config.render_config().width,
-        config.render_config().height,
-        image::ColorType::Rgba8,
+    let config_path = match config_path {
+        Some(path) => path,
+        None => {
+            println!(
+                "Usage: {} [--seed <seed>] [--sample-map <image>] <config.yaml>",
+                args[0]
+            );
+            return;
+        }
+    };
+    let config = Config::from_yaml(Path::new(config_path.as_str())).unwrap();
+    let mut scene = Scene::build_from_config(&config);
+    // コマンドラインで指定したシードは設定ファイルより優先する
+    if let Some(seed) = seed {
+        scene.set_seed(seed);
+    }
+    let film = scene.render_film();
+    save_film(
+        &film,
+        Path::new(config.output_name()),
+        config.output_format(),
+        scene.tone_mapping(),
     )
-    .expect("here");
+    .expect("failed to save image");
+
+    // 適応的サンプリングで各画素に使ったサンプル数を報告する
+    let samples = film.samples();
+    let render_config = config.render_config();
+    if render_config.max_samples.is_some()
+        || render_config.noise_threshold.is_some()
+        || render_config.time_limit.is_some()
+    {
+        let total: u64 = samples.iter().map(|n| *n as u64).sum();
+        println!(
+            "samples per pixel: min {}, mean {:.1}, max {}",
+            samples.iter().min().unwrap_or(&0),
+            total as f64 / samples.len().max(1) as f64,
+            samples.iter().max().unwrap_or(&0),
+        );
+    }
+    // サンプル数を、最も多い画素が白になる白黒画像にして保存する
+    if let Some(path) = sample_map {
+        let max = (*samples.iter().max().unwrap_or(&0)).max(1);
+        let gray: Vec<u8> = samples
+            .iter()
+            .map(|n| (*n as f64 / max as f64 * 255.0).round() as u8)
+            .collect();
+        image::save_buffer(
+            Path::new(&path),
+            &gray,
+            film.width(),
+            film.height(),
+            image::ColorType::L8,
+        )
+        .expect("failed to save sample map");
+    }
 }
@@ -0,0 +1,110 @@
+use crate::base::vec::Vec3;
+use crate::object::ray::Ray;
+
+// 各軸に平行な境界ボックス(Axis Aligned Bounding Box)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+  min: Vec3,
+  max: Vec3,
+}
+
+impl Aabb {
+  pub fn new(a: Vec3, b: Vec3) -> Self {
+    Self {
+      min: a.min(&b),
+      max: a.max(&b),
+    }
+  }
+
+  // 何も含まない箱。surrounding の単位元として使う
+  pub fn empty() -> Self {
+    Self {
+      min: Vec3::from_one(f64::INFINITY),
+      max: Vec3::from_one(f64::NEG_INFINITY),
+    }
+  }
+
+  pub fn min(&self) -> &Vec3 {
+    &self.min
+  }
+
+  pub fn max(&self) -> &Vec3 {
+    &self.max
+  }
+
+  pub fn centroid(&self) -> Vec3 {
+    (self.min + self.max) * 0.5
+  }
+
+  // 2つの箱を両方含む最小の箱
+  pub fn surrounding(&self, other: &Aabb) -> Aabb {
+    Self {
+      min: self.min.min(&other.min),
+      max: self.max.max(&other.max),
+    }
+  }
+
+  // 点を含むように広げた箱
+  pub fn expand(&self, p: &Vec3) -> Aabb {
+    Self {
+      min: self.min.min(p),
+      max: self.max.max(p),
+    }
+  }
+
+  // 最も長い辺の軸番号
+  pub fn longest_axis(&self) -> usize {
+    let d = self.max - self.min;
+    if d.get_x() > d.get_y() && d.get_x() > d.get_z() {
+      0
+    } else if d.get_y() > d.get_z() {
+      1
+    } else {
+      2
+    }
+  }
+
+  // スラブ法で [t0, t1] の範囲に光線との交差があるかを判定する
+  pub fn hit(&self, ray: &Ray, mut t0: f64, mut t1: f64) -> bool {
+    for axis in 0..3 {
+      let inv_d = 1.0 / ray.direction().get(axis);
+      let mut t_near = (self.min.get(axis) - ray.origin().get(axis)) * inv_d;
+      let mut t_far = (self.max.get(axis) - ray.origin().get(axis)) * inv_d;
+      if inv_d < 0.0 {
+        std::mem::swap(&mut t_near, &mut t_far);
+      }
+      t0 = t0.max(t_near);
+      t1 = t1.min(t_far);
+      if t1 < t0 {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+#[cfg(test)]
+mod aabb_test {
+  use super::*;
+  #[test]
+  fn test_hit() {
+    let aabb = Aabb::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(-1.0, -1.0, -1.0));
+    let r1 = Ray::new(Vec3::new(-3.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+    let r2 = Ray::new(Vec3::new(-3.0, 0.5, 0.5), Vec3::new(0.0, 1.0, 0.0));
+
+    assert!(aabb.hit(&r1, 0.0, f64::MAX));
+    assert!(!aabb.hit(&r1, 0.0, 1.0));
+    assert!(!aabb.hit(&r2, 0.0, f64::MAX));
+  }
+
+  #[test]
+  fn test_surrounding() {
+    let a = Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+    let b = Aabb::new(Vec3::new(-1.0, 0.5, 0.5), Vec3::new(0.5, 3.0, 0.5));
+    let s = a.surrounding(&b);
+    assert_eq!(*s.min(), Vec3::new(-1.0, 0.0, 0.0));
+    assert_eq!(*s.max(), Vec3::new(1.0, 3.0, 1.0));
+    assert_eq!(s.longest_axis(), 1);
+    assert_eq!(Aabb::empty().surrounding(&a), a);
+  }
+}
@@ -0,0 +1,204 @@
+use std::cmp::Ordering;
+
+use crate::object::aabb::Aabb;
+use crate::object::hit::HitInfo;
+use crate::object::ray::Ray;
+use crate::object::shape::Shape;
+
+// 葉ノードに入れる物体数の上限
+const MAX_LEAF_SIZE: usize = 2;
+// 走査用スタックの大きさ。中央値分割なので木の深さは log2(物体数) 程度に収まる
+const STACK_SIZE: usize = 64;
+
+#[derive(Debug)]
+enum BvhNode {
+  // indices[start..start + count] の物体を持つ葉
+  Leaf {
+    bbox: Aabb,
+    start: usize,
+    count: usize,
+  },
+  // 左の子は自身の直後に並び、右の子の位置だけを持つ
+  Interior {
+    bbox: Aabb,
+    right: usize,
+  },
+}
+
+impl BvhNode {
+  fn bbox(&self) -> &Aabb {
+    match self {
+      BvhNode::Leaf { bbox, .. } => bbox,
+      BvhNode::Interior { bbox, .. } => bbox,
+    }
+  }
+}
+
+// 境界ボリューム階層(Bounding Volume Hierarchy)
+// 物体そのものは持たず、構築時に渡された配列の添字を保持する。
+// そのため hit には構築時と同じ配列を渡す必要がある。
+#[derive(Debug)]
+pub struct Bvh {
+  nodes: Vec<BvhNode>,
+  indices: Vec<usize>,
+}
+
+impl Bvh {
+  pub fn build<S: Shape>(shapes: &[S]) -> Self {
+    let mut items: Vec<(usize, Aabb)> = shapes
+      .iter()
+      .enumerate()
+      .map(|(i, s)| (i, s.bounding_box()))
+      .collect();
+    let mut nodes = vec![];
+    if !items.is_empty() {
+      Self::build_node(&mut items, 0, &mut nodes);
+    }
+    Bvh {
+      nodes,
+      indices: items.iter().map(|(i, _)| *i).collect(),
+    }
+  }
+
+  // items を最長軸の中央値で2分割しながら再帰的にノードを作り、作ったノードの位置を返す
+  fn build_node(items: &mut [(usize, Aabb)], offset: usize, nodes: &mut Vec<BvhNode>) -> usize {
+    let bbox = items
+      .iter()
+      .fold(Aabb::empty(), |acc, (_, b)| acc.surrounding(b));
+    let node_index = nodes.len();
+    if items.len() <= MAX_LEAF_SIZE {
+      nodes.push(BvhNode::Leaf {
+        bbox,
+        start: offset,
+        count: items.len(),
+      });
+      return node_index;
+    }
+
+    let centroid_bounds = items
+      .iter()
+      .fold(Aabb::empty(), |acc, (_, b)| acc.expand(&b.centroid()));
+    let axis = centroid_bounds.longest_axis();
+    let mid = items.len() / 2;
+    items.select_nth_unstable_by(mid, |a, b| {
+      a.1
+        .centroid()
+        .get(axis)
+        .partial_cmp(&b.1.centroid().get(axis))
+        .unwrap_or(Ordering::Equal)
+    });
+
+    nodes.push(BvhNode::Interior { bbox, right: 0 });
+    let (left_items, right_items) = items.split_at_mut(mid);
+    Self::build_node(left_items, offset, nodes);
+    let right_index = Self::build_node(right_items, offset + mid, nodes);
+    if let BvhNode::Interior { right, .. } = &mut nodes[node_index] {
+      *right = right_index;
+    }
+    node_index
+  }
+
+  pub fn bounding_box(&self) -> Aabb {
+    self
+      .nodes
+      .first()
+      .map_or(Aabb::empty(), |node| *node.bbox())
+  }
+
+  // [t0, t1] の範囲で光線に最も近い物体とその衝突情報を返す
+  pub fn hit<'a, S: Shape>(
+    &self,
+    shapes: &'a [S],
+    ray: &Ray,
+    t0: f64,
+    mut t1: f64,
+  ) -> Option<(&'a S, HitInfo<'a>)> {
+    if self.nodes.is_empty() {
+      return None;
+    }
+    let mut closest = None;
+    let mut stack = [0usize; STACK_SIZE];
+    let mut stack_len = 1;
+    while stack_len > 0 {
+      stack_len -= 1;
+      let node_index = stack[stack_len];
+      let node = &self.nodes[node_index];
+      // 既に見つかった衝突より遠い箱は調べない
+      if !node.bbox().hit(ray, t0, t1) {
+        continue;
+      }
+      match node {
+        BvhNode::Leaf { start, count, .. } => {
+          for &i in &self.indices[*start..*start + *count] {
+            if let Some(hit_info) = shapes[i].hit(ray, t0, t1) {
+              t1 = hit_info.get_t();
+              closest = Some((&shapes[i], hit_info));
+            }
+          }
+        }
+        BvhNode::Interior { right, .. } => {
+          stack[stack_len] = *right;
+          stack[stack_len + 1] = node_index + 1;
+          stack_len += 2;
+        }
+      }
+    }
+    closest
+  }
+}
+
+#[cfg(test)]
+mod bvh_test {
+  use super::*;
+  use crate::base::math::{get_random_in_range, new_rand_gen, RandGen};
+  use crate::base::vec::Vec3;
+  use crate::object::material::Material;
+  use crate::object::sphere::Sphere;
+
+  fn random_spheres(n: usize, rng: &mut RandGen) -> Vec<Box<dyn Shape>> {
+    (0..n)
+      .map(|i| {
+        let center = Vec3::new(
+          get_random_in_range(rng, -10.0, 10.0),
+          get_random_in_range(rng, -10.0, 10.0),
+          get_random_in_range(rng, -10.0, 10.0),
+        );
+        Box::new(Sphere::new(
+          center,
+          get_random_in_range(rng, 0.1, 2.0),
+          format!("sphere {}", i),
+          Material::Black,
+        )) as Box<dyn Shape>
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_empty() {
+    let shapes: Vec<Sphere> = vec![];
+    let bvh = Bvh::build(&shapes);
+    let ray = Ray::new(Vec3::zero_vector(), Vec3::new(1.0, 0.0, 0.0));
+    assert!(bvh.hit(&shapes, &ray, 0.001, f64::MAX).is_none());
+  }
+
+  #[test]
+  fn test_same_as_linear_search() {
+    let mut rng = new_rand_gen(0);
+    let shapes = random_spheres(200, &mut rng);
+    let bvh = Bvh::build(&shapes);
+    for _ in 0..500 {
+      let ray = Ray::new(
+        Vec3::new(0.0, 0.0, -30.0),
+        Vec3::gen_random_vector(&mut rng) + Vec3::new(0.0, 0.0, 1.0),
+      );
+      let expected = shapes
+        .iter()
+        .filter_map(|s| s.hit(&ray, 0.001, f64::MAX).map(|h| (s.name(), h.get_t())))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+      let actual = bvh
+        .hit(&shapes, &ray, 0.001, f64::MAX)
+        .map(|(s, h)| (s.name(), h.get_t()));
+      assert_eq!(actual, expected);
+    }
+  }
+}
@@ -1,4 +1,3 @@
-use crate::base::math::get_random_in_range;
 use crate::base::vec::Vec3;
 use crate::config::scene_config::CameraConfig;
 use crate::object::ray::Ray;
@@ -71,12 +70,14 @@ impl Camera {
     )
   }
 
-  pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+  // s, t: 画角内の位置(左下が (0, 0)、右上が (1, 1))
+  // lens: レンズ上の位置を選ぶための 0 から 1 の2次元の点
+  pub fn get_ray(&self, s: f64, t: f64, lens: (f64, f64)) -> Ray {
     //Ray::new(
     //  self.origin.clone(),
     //  self.uvw.2 + self.uvw.0.dir(u) + self.uvw.1.dir(v) - self.origin.clone(),
     //)
-    let rd = Self::random_in_unit_disk().dir(self.lense_radius);
+    let rd = Self::square_to_disk(lens).dir(self.lense_radius);
     let offset = self.uvw.0.dir(rd.get_x()) + self.uvw.1.dir(rd.get_y());
     Ray::new(
       self.origin.clone() + offset,
@@ -85,17 +86,40 @@ impl Camera {
     )
   }
 
-  // 単位円上の点でかつある程度内側に近い点を返す
-  fn random_in_unit_disk() -> Vec3 {
-    let mut p: Vec3 = Vec3::new(10.0, 0.0, 0.0);
-    // 単位円の内部にある(=長さが1^2以下)のベクトルが生成されるまでサンプリング
-    while p.dot(&p) >= 1.0 {
-      p = Vec3::new(
-        get_random_in_range(-1.0, 1.0),
-        get_random_in_range(-1.0, 1.0),
-        0.0,
-      );
+  // 単位正方形の点を単位円の内部に写す(Shirley の同心円写像)
+  // 棄却法と違って1つの点から1つの点が決まるので、層別化した点の偏りの少なさが保たれる
+  fn square_to_disk((a, b): (f64, f64)) -> Vec3 {
+    let x = 2.0 * a - 1.0;
+    let y = 2.0 * b - 1.0;
+    if x == 0.0 && y == 0.0 {
+      return Vec3::zero_vector();
+    }
+    let (r, theta) = if x.abs() > y.abs() {
+      (x, std::f64::consts::FRAC_PI_4 * (y / x))
+    } else {
+      (
+        y,
+        std::f64::consts::FRAC_PI_2 - std::f64::consts::FRAC_PI_4 * (x / y),
+      )
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+  }
+}
+
+#[cfg(test)]
+mod camera_test {
+  use super::*;
+
+  #[test]
+  fn test_square_to_disk() {
+    assert_eq!(Camera::square_to_disk((0.5, 0.5)), Vec3::zero_vector());
+    let corner = Camera::square_to_disk((1.0, 1.0));
+    assert!((corner.norm() - 1.0).abs() < 1e-12);
+    let edge = Camera::square_to_disk((1.0, 0.5));
+    assert!((edge - Vec3::new(1.0, 0.0, 0.0)).norm() < 1e-12);
+    for n in 0..100 {
+      let p = Camera::square_to_disk((n as f64 * 0.0101, (n * 37 % 100) as f64 * 0.01));
+      assert!(p.norm() <= 1.0 + 1e-12);
     }
-    return p;
   }
 }
@@ -0,0 +1,299 @@
+use crate::base::math::{get_uniform_random, RandGen};
+use crate::base::vec::Vec3;
+use crate::object::aabb::Aabb;
+use crate::object::hit::HitInfo;
+use crate::object::material::Material;
+use crate::object::ray::Ray;
+use crate::object::shape::Shape;
+
+// 各軸に平行な面を持つ直方体
+pub struct Cuboid {
+  // 各成分が最小となる頂点
+  min: Vec3,
+  // 各成分が最大となる頂点
+  max: Vec3,
+  name: String,
+  material: Material,
+}
+
+impl Cuboid {
+  // left, right は対角にある2頂点で、成分の大小は問わない
+  pub fn new(left: Vec3, right: Vec3, name: String, material: Material) -> Self {
+    Self {
+      min: left.min(&right),
+      max: left.max(&right),
+      name,
+      material,
+    }
+  }
+
+  pub fn min(&self) -> Vec3 {
+    self.min
+  }
+
+  pub fn max(&self) -> Vec3 {
+    self.max
+  }
+
+  // axis に垂直な面上の点 p の UV 座標。残りの2軸の位置を 0 から 1 にしたもの
+  fn face_uv(&self, p: &Vec3, axis: usize) -> (f64, f64) {
+    let ratio = |a: usize| {
+      let size = self.max.get(a) - self.min.get(a);
+      if size > 0.0 {
+        (p.get(a) - self.min.get(a)) / size
+      } else {
+        0.0
+      }
+    };
+    (ratio((axis + 1) % 3), ratio((axis + 2) % 3))
+  }
+
+  // スラブ法: 各軸について光線が2枚の面の間にいる区間を求め、その共通部分を取る
+  // 区間の両端の t と、それぞれが乗っている面の軸を返す
+  fn slab(&self, ray: &Ray) -> Option<(f64, usize, f64, usize)> {
+    let mut t_near = f64::NEG_INFINITY;
+    let mut t_far = f64::INFINITY;
+    let mut near_axis = 0;
+    let mut far_axis = 0;
+    for axis in 0..3 {
+      let inv_d = 1.0 / ray.direction().get(axis);
+      let mut t0 = (self.min.get(axis) - ray.origin().get(axis)) * inv_d;
+      let mut t1 = (self.max.get(axis) - ray.origin().get(axis)) * inv_d;
+      if inv_d < 0.0 {
+        std::mem::swap(&mut t0, &mut t1);
+      }
+      if t0 > t_near {
+        t_near = t0;
+        near_axis = axis;
+      }
+      if t1 < t_far {
+        t_far = t1;
+        far_axis = axis;
+      }
+      if t_near > t_far {
+        return None;
+      }
+    }
+    Some((t_near, near_axis, t_far, far_axis))
+  }
+
+  // 各面の (軸, 面の座標, 面積)
+  fn faces(&self) -> Vec<(usize, f64, f64)> {
+    let size = self.max - self.min;
+    (0..3)
+      .flat_map(|axis| {
+        let area = size.get((axis + 1) % 3) * size.get((axis + 2) % 3);
+        [
+          (axis, self.min.get(axis), area),
+          (axis, self.max.get(axis), area),
+        ]
+      })
+      .collect()
+  }
+
+  // axis 方向の単位ベクトルに符号 sign を掛けたもの
+  fn axis_normal(axis: usize, sign: f64) -> Vec3 {
+    match axis {
+      0 => Vec3::new(sign, 0.0, 0.0),
+      1 => Vec3::new(0.0, sign, 0.0),
+      _ => Vec3::new(0.0, 0.0, sign),
+    }
+  }
+}
+
+impl Shape for Cuboid {
+  fn hit(&self, ray: &Ray, lower_range: f64, upper_range: f64) -> Option<HitInfo<'_>> {
+    let (t_near, near_axis, t_far, far_axis) = self.slab(ray)?;
+
+    // 入射側の面が範囲外なら(=光線の始点が箱の内部にある)、出射側の面を使う
+    let (t, axis, outward_normal) = if lower_range <= t_near && t_near <= upper_range {
+      let sign = -ray.direction().get(near_axis).signum();
+      (t_near, near_axis, Self::axis_normal(near_axis, sign))
+    } else if lower_range <= t_far && t_far <= upper_range {
+      let sign = ray.direction().get(far_axis).signum();
+      (t_far, far_axis, Self::axis_normal(far_axis, sign))
+    } else {
+      return None;
+    };
+
+    let pos = ray.at(t);
+    let mut hit_info = HitInfo::new(t, pos, outward_normal, &self.material, ray);
+    hit_info.set_front_face(ray, &outward_normal);
+    let (u, v) = self.face_uv(&pos, axis);
+    Some(hit_info.with_uv(u, v))
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    Aabb::new(self.min, self.max)
+  }
+
+  fn name(&self) -> &str {
+    self.name.as_str()
+  }
+
+  fn is_light(&self) -> bool {
+    self.material.is_emissive()
+  }
+
+  // 表面積に比例して面を選び、その面上の一様な点へ向かう方向を返す
+  fn sample_direction(&self, origin: &Vec3, rng: &mut RandGen) -> Option<Vec3> {
+    let faces = self.faces();
+    let total_area: f64 = faces.iter().map(|f| f.2).sum();
+    if total_area <= 0.0 {
+      return None;
+    }
+    let mut pick = get_uniform_random(rng) * total_area;
+    let &(axis, plane, _) = faces
+      .iter()
+      .find(|f| {
+        pick -= f.2;
+        pick <= 0.0
+      })
+      .unwrap_or(&faces[faces.len() - 1]);
+    let mut point = [0.0; 3];
+    point[axis] = plane;
+    for a in [(axis + 1) % 3, (axis + 2) % 3] {
+      point[a] = self.min.get(a) + get_uniform_random(rng) * (self.max.get(a) - self.min.get(a));
+    }
+    let direction = Vec3::new(point[0], point[1], point[2]) - *origin;
+    if direction.near_zero() {
+      return None;
+    }
+    Some(direction)
+  }
+
+  // direction の光線が通る表面上の点(入口と出口)を選ぶ確率密度を、立体角あたりに直して足し合わせる
+  fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+    let total_area: f64 = self.faces().iter().map(|f| f.2).sum();
+    if total_area <= 0.0 {
+      return 0.0;
+    }
+    let dir = direction.normalize();
+    let Some((t_near, near_axis, t_far, far_axis)) = self.slab(&Ray::new(*origin, dir)) else {
+      return 0.0;
+    };
+    [(t_near, near_axis), (t_far, far_axis)]
+      .iter()
+      .filter(|(t, _)| *t > 0.001)
+      .map(|(t, axis)| {
+        let cos = dir.get(*axis).abs();
+        if cos > 0.0 {
+          t * t / (cos * total_area)
+        } else {
+          0.0
+        }
+      })
+      .sum()
+  }
+}
+
+#[cfg(test)]
+mod cuboid_test {
+  use super::*;
+
+  fn unit_cuboid() -> Cuboid {
+    Cuboid::new(
+      Vec3::new(1.0, 1.0, 1.0),
+      Vec3::new(-1.0, -1.0, -1.0),
+      "sample".to_string(),
+      Material::Black,
+    )
+  }
+
+  #[test]
+  fn test_hit() {
+    let cuboid = unit_cuboid();
+    let r1 = Ray::new(Vec3::new(-3.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+    let r2 = Ray::new(Vec3::new(-3.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let r3 = Ray::new(Vec3::new(-3.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+
+    let hit = cuboid.hit(&r1, 0.001, f64::MAX).unwrap();
+    assert_eq!(hit.get_t(), 2.0);
+    assert_eq!(*hit.get_poisition(), Vec3::new(-1.0, 0.0, 0.0));
+    assert_eq!(*hit.get_normal(), Vec3::new(-1.0, 0.0, 0.0));
+    assert!(hit.front_face());
+    assert!(cuboid.hit(&r2, 0.001, f64::MAX).is_none());
+    assert!(cuboid.hit(&r3, 0.001, f64::MAX).is_none());
+    assert_eq!(hit.get_uv(), (0.5, 0.5));
+
+    let corner = Ray::new(Vec3::new(0.5, -0.5, 3.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit = cuboid.hit(&corner, 0.001, f64::MAX).unwrap();
+    assert_eq!(hit.get_uv(), (0.75, 0.25));
+  }
+
+  #[test]
+  fn test_hit_each_face() {
+    let cuboid = unit_cuboid();
+    let dirs = [
+      Vec3::new(1.0, 0.0, 0.0),
+      Vec3::new(0.0, 1.0, 0.0),
+      Vec3::new(0.0, 0.0, 1.0),
+    ];
+    for dir in dirs.iter() {
+      for sign in [1.0, -1.0] {
+        let d = *dir * sign;
+        let ray = Ray::new(d * -5.0, d);
+        let hit = cuboid.hit(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.get_t(), 4.0);
+        assert_eq!(*hit.get_normal(), d * -1.0);
+      }
+    }
+  }
+
+  #[test]
+  fn test_hit_from_inside() {
+    let cuboid = unit_cuboid();
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0));
+    let hit = cuboid.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert_eq!(hit.get_t(), 0.5);
+    assert_eq!(*hit.get_poisition(), Vec3::new(0.0, 0.0, 1.0));
+    // 内側から当たるので法線は光線と逆向き(内向き)になる
+    assert_eq!(*hit.get_normal(), Vec3::new(0.0, 0.0, -1.0));
+    assert!(!hit.front_face());
+  }
+
+  #[test]
+  fn test_sample_direction() {
+    use crate::base::math::new_rand_gen;
+    // 立方体と、厚さ0の板(天井の面光源など)
+    let emit = Vec3::from_one(1.0);
+    let boxes = [
+      Cuboid::new(
+        Vec3::new(1.0, 2.0, 0.0),
+        Vec3::new(2.0, 3.0, 1.5),
+        "cube".to_string(),
+        Material::DiffuseLight { emit },
+      ),
+      Cuboid::new(
+        Vec3::new(-1.0, -1.0, 2.0),
+        Vec3::new(1.0, 1.0, 2.0),
+        "panel".to_string(),
+        Material::DiffuseLight { emit },
+      ),
+    ];
+    let origin = Vec3::new(0.0, 0.0, 0.5);
+    let mut rng = new_rand_gen(2);
+    for light in boxes.iter() {
+      assert!(light.is_light());
+      for _ in 0..1000 {
+        let dir = light.sample_direction(&origin, &mut rng).unwrap();
+        assert!(light.direction_pdf(&origin, &dir) > 0.0);
+      }
+      // 全方向で pdf を積分すると 1 になる
+      let n = 400000;
+      let mut sum = 0.0;
+      for _ in 0..n {
+        let dir = Vec3::gen_random_vector_in_unit_shpere(&mut rng);
+        sum += light.direction_pdf(&origin, &dir) * 4.0 * std::f64::consts::PI;
+      }
+      let integral = sum / n as f64;
+      assert!(
+        (integral - 1.0).abs() < 0.05,
+        "{}: {}",
+        light.name(),
+        integral
+      );
+    }
+    assert!(!unit_cuboid().is_light());
+  }
+}
@@ -3,7 +3,7 @@ use crate::object::material::Material;
 use crate::object::ray::Ray;
 
 #[derive(Debug)]
-pub struct HitInfo {
+pub struct HitInfo<'a> {
   // 光線のパラメタ
   t: f64,
   // 光線が物体と衝突した位置
@@ -13,11 +13,20 @@ pub struct HitInfo {
   // 衝突点から外側に光線が出ているか
   front_face: bool,
   // 衝突した物体の材質情報
-  hit_material: Material,
+  hit_material: &'a Material,
+  // テクスチャを貼るための表面上の座標
+  u: f64,
+  v: f64,
 }
 
-impl HitInfo {
-  pub fn new(t: f64, position: Vec3, normal: Vec3, hit_material: Material, hit_ray: &Ray) -> Self {
+impl<'a> HitInfo<'a> {
+  pub fn new(
+    t: f64,
+    position: Vec3,
+    normal: Vec3,
+    hit_material: &'a Material,
+    hit_ray: &Ray,
+  ) -> Self {
     let front_face = hit_ray.direction().dot(&normal) < 0.0;
     HitInfo {
       t: t,
@@ -25,6 +34,8 @@ impl HitInfo {
       normal: normal,
       hit_material: hit_material,
       front_face: front_face,
+      u: 0.0,
+      v: 0.0,
     }
   }
 
@@ -40,8 +51,16 @@ impl HitInfo {
     &self.normal
   }
 
-  pub fn get_hit_material(&self) -> &Material {
-    &self.hit_material
+  pub fn get_hit_material(&self) -> &'a Material {
+    self.hit_material
+  }
+
+  pub fn get_uv(&self) -> (f64, f64) {
+    (self.u, self.v)
+  }
+
+  pub fn with_uv(self, u: f64, v: f64) -> Self {
+    HitInfo { u, v, ..self }
   }
 
   pub fn set_front_face(&mut self, ray: &Ray, outward_normal: &Vec3) {
@@ -54,6 +73,16 @@ impl HitInfo {
     };
   }
 
+  // 座標変換した物体のために、衝突位置と法線だけを置き換える
+  // 法線は光線と逆向きにそろえたものを渡す
+  pub fn with_position_normal(self, position: Vec3, normal: Vec3) -> Self {
+    HitInfo {
+      position,
+      normal,
+      ..self
+    }
+  }
+
   pub fn front_face(&self) -> bool {
     self.front_face
   }
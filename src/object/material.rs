@@ -1,9 +1,440 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::base::math::{get_uniform_random, RandGen};
+use crate::base::onb::Onb;
 use crate::base::vec::Vec3;
+use crate::object::hit::HitInfo;
+use crate::object::microfacet::MicrofacetBsdf;
+use crate::object::ray::Ray;
+use crate::object::texture::Texture;
+
+// ライブラリの利用者が独自の材質を定義するためのトレイト
+// Material::Custom に入れれば、どの形状にも組み込みの材質と同じように使える
+pub trait CustomMaterial: std::fmt::Debug + Send + Sync {
+  // 散乱しない(光を吸収する)場合は None を返す
+  fn scatter(
+    &self,
+    incoming_ray: &Ray,
+    hit_info: &HitInfo,
+    rng: &mut RandGen,
+  ) -> Option<(Ray, Vec3)>;
+
+  fn emitted(&self, _hit_info: &HitInfo) -> Vec3 {
+    Vec3::zero_vector()
+  }
+
+  // 光源として直接サンプリングさせる場合は true を返す
+  fn is_emissive(&self) -> bool {
+    false
+  }
 
-#[derive(Debug, Copy, Clone)]
+  // 光源へ向かう方向 direction に対する BSDF x cosθ と、scatter がその方向を選ぶ確率密度
+  // 値を計算できない材質は None のままにすると、光源の直接サンプリングを行わない
+  fn bsdf(
+    &self,
+    _incoming_ray: &Ray,
+    _hit_info: &HitInfo,
+    _direction: &Vec3,
+  ) -> Option<(Vec3, f64)> {
+    None
+  }
+}
+
+#[derive(Debug, Clone)]
 pub enum Material {
-  Lambertian { albedo: Vec3 },
-  Metal { albedo: Vec3, fuzzy: f64 },
-  Dielectric { refraction_index: f64 },
+  Lambertian {
+    albedo: Texture,
+  },
+  Metal {
+    albedo: Texture,
+    fuzzy: f64,
+  },
+  // absorption は内部を単位長さ進むごとの吸収係数。0 なら無色透明
+  Dielectric {
+    refraction_index: f64,
+    absorption: Vec3,
+  },
+  // GGX マイクロファセットによる金属/粗さモデル
+  Microfacet {
+    base_color: Texture,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+  },
+  // 自ら光を放つ材質。emit は放射輝度(色 x 強さ)
+  DiffuseLight {
+    emit: Vec3,
+  },
   Black,
+  Custom(Arc<dyn CustomMaterial>),
+}
+
+impl Material {
+  // 入射光線と衝突情報から散乱光線と減衰率を求める
+  // 形状によらず材質だけで決まる
+  pub fn scatter(
+    &self,
+    incoming_ray: &Ray,
+    hit_info: &HitInfo,
+    rng: &mut RandGen,
+  ) -> Option<(Ray, Vec3)> {
+    match self {
+      Material::Lambertian { albedo } => {
+        // 法線は光線が来た側を向いているので、その半球に cosθ に比例した密度で散乱させる
+        let scatter_dir =
+          Onb::from_w(hit_info.get_normal()).local(&Vec3::gen_random_cosine_direction(rng));
+        let scattered = Ray::new(*hit_info.get_poisition(), scatter_dir);
+        Some((scattered, Self::albedo_at(albedo, hit_info)))
+      }
+      Material::Metal { albedo, fuzzy } => {
+        let reflected = Vec3::reflect(&incoming_ray.direction().normalize(), hit_info.get_normal())
+          + Vec3::gen_random_vector_in_unit_shpere(rng) * fuzzy.clamp(0.0, 1.0);
+        let scatterd = Ray::new(*hit_info.get_poisition(), reflected);
+        if scatterd.direction().dot(hit_info.get_normal()) > 0.0 {
+          return Some((scatterd, Self::albedo_at(albedo, hit_info)));
+        }
+        None
+      }
+      Material::Dielectric {
+        refraction_index,
+        absorption,
+      } => {
+        let refraction_ratio = if hit_info.front_face() {
+          1.0 / refraction_index
+        } else {
+          *refraction_index
+        };
+
+        let unit_dir = incoming_ray.direction().normalize();
+        let normal = hit_info.get_normal();
+        let cos_theta = (-unit_dir.dot(normal)).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        // 全反射になる場合と、フレネル反射率の確率で反射する
+        let direction = if refraction_ratio * sin_theta > 1.0
+          || Self::reflectance(cos_theta, refraction_ratio) > get_uniform_random(rng)
+        {
+          Vec3::reflect(&unit_dir, normal)
+        } else {
+          Vec3::refract(&unit_dir, normal, refraction_ratio)
+        };
+
+        // 内側から当たった光線は媒質の中を通ってきたので、その距離に応じて減衰させる(Beer–Lambert)
+        let attenuation = if hit_info.front_face() {
+          Vec3::from_one(1.0)
+        } else {
+          let distance = hit_info.get_t() * incoming_ray.direction().norm();
+          Vec3::new(
+            (-absorption.get_x() * distance).exp(),
+            (-absorption.get_y() * distance).exp(),
+            (-absorption.get_z() * distance).exp(),
+          )
+        };
+        let scatterd = Ray::new(*hit_info.get_poisition(), direction);
+        Some((scatterd, attenuation))
+      }
+      Material::Microfacet {
+        base_color,
+        metallic,
+        roughness,
+        specular,
+      } => {
+        let bsdf = MicrofacetBsdf::new(
+          Self::albedo_at(base_color, hit_info),
+          *metallic,
+          *roughness,
+          *specular,
+        );
+        let onb = Onb::from_w(hit_info.get_normal());
+        let v = onb.to_local(&(incoming_ray.direction().normalize() * -1.0));
+        let l = bsdf.sample(&v, rng)?;
+        let pdf = bsdf.pdf(&v, &l);
+        if pdf <= 0.0 {
+          return None;
+        }
+        let scattered = Ray::new(*hit_info.get_poisition(), onb.local(&l));
+        Some((scattered, bsdf.eval(&v, &l) / pdf))
+      }
+      Material::DiffuseLight { .. } => None,
+      Material::Custom(custom) => custom.scatter(incoming_ray, hit_info, rng),
+      Material::Black => Some((
+        Ray::new(Vec3::zero_vector(), Vec3::zero_vector()),
+        Vec3::zero_vector(),
+      )),
+    }
+  }
+
+  // Schlick の近似によるフレネル反射率
+  // 屈折率の高い側から入射する場合は、屈折側の角度で評価する
+  fn reflectance(cos_theta: f64, refraction_ratio: f64) -> f64 {
+    let cos = if refraction_ratio > 1.0 {
+      let sin2_t = refraction_ratio * refraction_ratio * (1.0 - cos_theta * cos_theta);
+      (1.0 - sin2_t).max(0.0).sqrt()
+    } else {
+      cos_theta
+    };
+    let r0 = ((1.0 - refraction_ratio) / (1.0 + refraction_ratio)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+  }
+
+  fn albedo_at(albedo: &Texture, hit_info: &HitInfo) -> Vec3 {
+    let (u, v) = hit_info.get_uv();
+    albedo.value(u, v, hit_info.get_poisition())
+  }
+
+  // 光源の直接サンプリングで使う、方向 direction に対する BSDF x cosθ と scatter の確率密度
+  // 鏡面反射や屈折のように特定の方向にしか散乱しない材質は None
+  pub fn bsdf(
+    &self,
+    incoming_ray: &Ray,
+    hit_info: &HitInfo,
+    direction: &Vec3,
+  ) -> Option<(Vec3, f64)> {
+    match self {
+      Material::Lambertian { albedo } => {
+        let cos = direction.normalize().dot(hit_info.get_normal());
+        if cos <= 0.0 {
+          return Some((Vec3::zero_vector(), 0.0));
+        }
+        Some((Self::albedo_at(albedo, hit_info) * (cos / PI), cos / PI))
+      }
+      Material::Microfacet {
+        base_color,
+        metallic,
+        roughness,
+        specular,
+      } => {
+        let bsdf = MicrofacetBsdf::new(
+          Self::albedo_at(base_color, hit_info),
+          *metallic,
+          *roughness,
+          *specular,
+        );
+        let onb = Onb::from_w(hit_info.get_normal());
+        let v = onb.to_local(&(incoming_ray.direction().normalize() * -1.0));
+        let l = onb.to_local(&direction.normalize());
+        Some((bsdf.eval(&v, &l), bsdf.pdf(&v, &l)))
+      }
+      Material::Custom(custom) => custom.bsdf(incoming_ray, hit_info, direction),
+      _ => None,
+    }
+  }
+
+  // 光を放つ材質か。光源として直接サンプリングする対象になる
+  pub fn is_emissive(&self) -> bool {
+    match self {
+      Material::DiffuseLight { emit } => emit.norm() > 0.0,
+      Material::Custom(custom) => custom.is_emissive(),
+      _ => false,
+    }
+  }
+
+  // 衝突点から放たれる光
+  pub fn emitted(&self, hit_info: &HitInfo) -> Vec3 {
+    match self {
+      Material::DiffuseLight { emit } => *emit,
+      Material::Custom(custom) => custom.emitted(hit_info),
+      _ => Vec3::zero_vector(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod material_test {
+  use super::*;
+  use crate::base::math::new_rand_gen;
+
+  #[test]
+  fn test_diffuse_light() {
+    let light = Material::DiffuseLight {
+      emit: Vec3::new(4.0, 2.0, 1.0),
+    };
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit_info = HitInfo::new(
+      1.0,
+      Vec3::new(0.0, 0.0, 1.0),
+      Vec3::new(0.0, 0.0, 1.0),
+      &light,
+      &ray,
+    );
+    assert_eq!(light.emitted(&hit_info), Vec3::new(4.0, 2.0, 1.0));
+    assert!(light
+      .scatter(&ray, &hit_info, &mut new_rand_gen(0))
+      .is_none());
+
+    let lambertian = Material::Lambertian {
+      albedo: Vec3::new(0.5, 0.5, 0.5).into(),
+    };
+    assert_eq!(lambertian.emitted(&hit_info), Vec3::zero_vector());
+  }
+
+  #[test]
+  fn test_lambertian_distribution() {
+    // 法線が軸に平行でない面に、裏側から光線を当てる
+    let outward_normal = Vec3::new(1.0, -2.0, 2.0).normalize();
+    let ray = Ray::new(outward_normal * -2.0, outward_normal);
+    let lambertian = Material::Lambertian {
+      albedo: Vec3::new(0.5, 0.5, 0.5).into(),
+    };
+    let mut hit_info = HitInfo::new(1.0, Vec3::zero_vector(), outward_normal, &lambertian, &ray);
+    hit_info.set_front_face(&ray, &outward_normal);
+    let normal = *hit_info.get_normal();
+    assert!(normal.dot(&outward_normal) < 0.0);
+
+    let mut rng = new_rand_gen(7);
+    let n = 200000;
+    let bins = 10;
+    let mut histogram = vec![0; bins];
+    let mut sum_cos = 0.0;
+    let mut sum_tangent = Vec3::zero_vector();
+    for _ in 0..n {
+      let (scattered, attenuation) = lambertian.scatter(&ray, &hit_info, &mut rng).unwrap();
+      assert_eq!(attenuation, Vec3::new(0.5, 0.5, 0.5));
+      let dir = scattered.direction().normalize();
+      let cos = dir.dot(&normal);
+      // 光線が来た側の半球にだけ散乱する
+      assert!(cos >= 0.0);
+      sum_cos += cos;
+      sum_tangent = sum_tangent + (dir - normal * cos);
+      // cosθ に比例する密度なら cos²θ は 0 から 1 の一様分布になる
+      histogram[((cos * cos * bins as f64) as usize).min(bins - 1)] += 1;
+    }
+    // E[cosθ] = 2/3
+    assert!((sum_cos / n as f64 - 2.0 / 3.0).abs() < 0.005);
+    // 法線まわりに偏りがない
+    assert!((sum_tangent / n as f64).norm() < 0.01);
+    let expected = n as f64 / bins as f64;
+    for count in histogram.iter() {
+      assert!((*count as f64 - expected).abs() < 0.05 * expected);
+    }
+  }
+
+  fn glass(absorption: Vec3) -> Material {
+    Material::Dielectric {
+      refraction_index: 1.5,
+      absorption,
+    }
+  }
+
+  #[test]
+  fn test_dielectric_fresnel() {
+    // 垂直に入射した光の反射率は ((n - 1) / (n + 1))^2 = 0.04
+    let glass = glass(Vec3::zero_vector());
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit_info = HitInfo::new(
+      2.0,
+      Vec3::zero_vector(),
+      Vec3::new(0.0, 0.0, 1.0),
+      &glass,
+      &ray,
+    );
+    let mut rng = new_rand_gen(3);
+    let n = 100000;
+    let mut reflected = 0;
+    for _ in 0..n {
+      let (scattered, attenuation) = glass.scatter(&ray, &hit_info, &mut rng).unwrap();
+      assert_eq!(attenuation, Vec3::from_one(1.0));
+      if scattered.direction().get_z() > 0.0 {
+        reflected += 1;
+      }
+    }
+    assert!((reflected as f64 / n as f64 - 0.04).abs() < 0.005);
+  }
+
+  #[test]
+  fn test_dielectric_total_internal_reflection() {
+    // 内側から臨界角(約41.8度)より浅い角度で当たると必ず反射する
+    let glass = glass(Vec3::zero_vector());
+    let outward_normal = Vec3::new(0.0, 0.0, 1.0);
+    let ray = Ray::new(Vec3::new(-2.0, 0.0, -1.0), Vec3::new(2.0, 0.0, 1.0));
+    let mut hit_info = HitInfo::new(1.0, Vec3::zero_vector(), outward_normal, &glass, &ray);
+    hit_info.set_front_face(&ray, &outward_normal);
+    assert!(!hit_info.front_face());
+    let mut rng = new_rand_gen(3);
+    for _ in 0..1000 {
+      let (scattered, _) = glass.scatter(&ray, &hit_info, &mut rng).unwrap();
+      let dir = scattered.direction();
+      assert!(dir.get_x().is_finite() && dir.get_z().is_finite());
+      assert!(dir.get_z() < 0.0);
+    }
+  }
+
+  #[test]
+  fn test_dielectric_absorption() {
+    let glass = glass(Vec3::new(0.5, 0.0, 1.0));
+    let outward_normal = Vec3::new(0.0, 0.0, 1.0);
+    // 内部を長さ2進んでから出ていく光線
+    let ray = Ray::new(Vec3::new(0.0, 0.0, -2.0), Vec3::new(0.0, 0.0, 0.5));
+    let mut hit_info = HitInfo::new(4.0, Vec3::zero_vector(), outward_normal, &glass, &ray);
+    hit_info.set_front_face(&ray, &outward_normal);
+    let (_, attenuation) = glass
+      .scatter(&ray, &hit_info, &mut new_rand_gen(0))
+      .unwrap();
+    assert!((attenuation - Vec3::new((-1.0f64).exp(), 1.0, (-2.0f64).exp())).norm() < 1e-12);
+
+    // 外側から入るときは吸収しない
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit_info = HitInfo::new(2.0, Vec3::zero_vector(), outward_normal, &glass, &ray);
+    let (_, attenuation) = glass
+      .scatter(&ray, &hit_info, &mut new_rand_gen(0))
+      .unwrap();
+    assert_eq!(attenuation, Vec3::from_one(1.0));
+  }
+
+  #[test]
+  fn test_bsdf_matches_scatter() {
+    // scatter が返す減衰率は、選んだ方向の BSDF x cosθ / pdf に等しい
+    let ray = Ray::new(Vec3::new(0.3, 0.0, 2.0), Vec3::new(-0.3, 0.0, -2.0));
+    let materials = [
+      Material::Lambertian {
+        albedo: Vec3::new(0.2, 0.4, 0.6).into(),
+      },
+      Material::Microfacet {
+        base_color: Vec3::new(0.9, 0.6, 0.3).into(),
+        metallic: 0.5,
+        roughness: 0.4,
+        specular: 0.5,
+      },
+    ];
+    let mut rng = new_rand_gen(9);
+    for material in materials.iter() {
+      let hit_info = HitInfo::new(
+        1.0,
+        Vec3::zero_vector(),
+        Vec3::new(0.0, 0.0, 1.0),
+        material,
+        &ray,
+      );
+      for _ in 0..100 {
+        if let Some((scattered, attenuation)) = material.scatter(&ray, &hit_info, &mut rng) {
+          let (f_cos, pdf) = material
+            .bsdf(&ray, &hit_info, scattered.direction())
+            .unwrap();
+          assert!((f_cos / pdf - attenuation).norm() < 1e-9);
+        }
+      }
+      // 面の裏側へは散乱しない
+      let (f_cos, _) = material
+        .bsdf(&ray, &hit_info, &Vec3::new(0.0, 1.0, -1.0))
+        .unwrap();
+      assert_eq!(f_cos, Vec3::zero_vector());
+    }
+    // 鏡面反射や屈折は光源の直接サンプリングに使わない
+    let glass = glass(Vec3::zero_vector());
+    let hit_info = HitInfo::new(
+      1.0,
+      Vec3::zero_vector(),
+      Vec3::new(0.0, 0.0, 1.0),
+      &glass,
+      &ray,
+    );
+    assert!(glass
+      .bsdf(&ray, &hit_info, &Vec3::new(0.0, 0.0, 1.0))
+      .is_none());
+    assert!(!glass.is_emissive());
+    assert!(Material::DiffuseLight {
+      emit: Vec3::from_one(1.0)
+    }
+    .is_emissive());
+  }
 }
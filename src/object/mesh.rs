@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use crate::base::vec::Vec3;
+use crate::object::aabb::Aabb;
+use crate::object::bvh::Bvh;
+use crate::object::hit::HitInfo;
+use crate::object::material::Material;
+use crate::object::ray::Ray;
+use crate::object::shape::Shape;
+use crate::object::triangle::Triangle;
+
+// メッシュの頂点データ。同じメッシュの三角形はこれを共有する
+#[derive(Debug)]
+pub struct MeshData {
+  name: String,
+  positions: Vec<Vec3>,
+  // 頂点ごとの法線。指定した場合は positions と同じ数だけ必要
+  normals: Option<Vec<Vec3>>,
+}
+
+impl MeshData {
+  pub fn new(name: String, positions: Vec<Vec3>, normals: Option<Vec<Vec3>>) -> Self {
+    if let Some(normals) = &normals {
+      assert_eq!(
+        normals.len(),
+        positions.len(),
+        "mesh {} must have one normal per vertex",
+        name
+      );
+    }
+    Self {
+      name,
+      positions,
+      normals,
+    }
+  }
+
+  pub fn name(&self) -> &str {
+    self.name.as_str()
+  }
+
+  pub fn positions(&self) -> &[Vec3] {
+    &self.positions
+  }
+
+  pub fn normals(&self) -> Option<&[Vec3]> {
+    self.normals.as_deref()
+  }
+}
+
+// 三角形の集まり。内部にBVHを持つので、三角形の多いモデルでも1つの物体として扱える
+pub struct Mesh {
+  triangles: Vec<Triangle>,
+  bvh: Bvh,
+  name: String,
+}
+
+impl Mesh {
+  // faces の各要素は positions の添字3つ(反時計回りが表)
+  pub fn new(
+    name: String,
+    positions: Vec<Vec3>,
+    normals: Option<Vec<Vec3>>,
+    faces: &[[usize; 3]],
+    material: Material,
+  ) -> Self {
+    let data = Arc::new(MeshData::new(name.clone(), positions, normals));
+    let material = Arc::new(material);
+    let triangles = faces
+      .iter()
+      .map(|face| Triangle::from_mesh(data.clone(), *face, material.clone()))
+      .collect();
+    Self::from_triangles(name, triangles)
+  }
+
+  // 三角形ごとに材質が違う場合などに、作成済みの三角形からメッシュを作る
+  pub fn from_triangles(name: String, triangles: Vec<Triangle>) -> Self {
+    let bvh = Bvh::build(&triangles);
+    Self {
+      triangles,
+      bvh,
+      name,
+    }
+  }
+
+  pub fn triangles(&self) -> &[Triangle] {
+    &self.triangles
+  }
+}
+
+impl Shape for Mesh {
+  fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo<'_>> {
+    self
+      .bvh
+      .hit(&self.triangles, ray, t0, t1)
+      .map(|(_, hit_info)| hit_info)
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    self.bvh.bounding_box()
+  }
+
+  fn name(&self) -> &str {
+    self.name.as_str()
+  }
+}
+
+#[cfg(test)]
+mod mesh_test {
+  use super::*;
+
+  // 原点を中心とした一辺2の立方体の表面
+  fn cube() -> Mesh {
+    let positions = vec![
+      Vec3::new(-1.0, -1.0, -1.0),
+      Vec3::new(1.0, -1.0, -1.0),
+      Vec3::new(1.0, 1.0, -1.0),
+      Vec3::new(-1.0, 1.0, -1.0),
+      Vec3::new(-1.0, -1.0, 1.0),
+      Vec3::new(1.0, -1.0, 1.0),
+      Vec3::new(1.0, 1.0, 1.0),
+      Vec3::new(-1.0, 1.0, 1.0),
+    ];
+    let faces = [
+      [0, 2, 1],
+      [0, 3, 2],
+      [4, 5, 6],
+      [4, 6, 7],
+      [0, 1, 5],
+      [0, 5, 4],
+      [2, 3, 7],
+      [2, 7, 6],
+      [1, 2, 6],
+      [1, 6, 5],
+      [0, 4, 7],
+      [0, 7, 3],
+    ];
+    Mesh::new("cube".to_string(), positions, None, &faces, Material::Black)
+  }
+
+  #[test]
+  fn test_hit() {
+    let mesh = cube();
+    assert_eq!(mesh.triangles().len(), 12);
+    let ray = Ray::new(Vec3::new(0.3, 0.2, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit = mesh.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert_eq!(hit.get_t(), 4.0);
+    assert_eq!(*hit.get_normal(), Vec3::new(0.0, 0.0, 1.0));
+    assert!(hit.front_face());
+
+    let miss = Ray::new(Vec3::new(3.0, 0.2, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(mesh.hit(&miss, 0.001, f64::MAX).is_none());
+  }
+
+  #[test]
+  fn test_hit_from_inside() {
+    let mesh = cube();
+    let ray = Ray::new(Vec3::new(0.1, 0.2, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+    let hit = mesh.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert_eq!(hit.get_t(), 1.1);
+    assert!(!hit.front_face());
+  }
+
+  #[test]
+  fn test_shared_vertices() {
+    let mesh = cube();
+    let first = &mesh.triangles()[0];
+    let second = &mesh.triangles()[1];
+    assert_eq!(first.vertex(0), second.vertex(0));
+    assert_eq!(first.vertex(1), second.vertex(2));
+  }
+
+  #[test]
+  #[should_panic]
+  fn test_invalid_face() {
+    Mesh::new(
+      "invalid".to_string(),
+      vec![Vec3::zero_vector(); 3],
+      None,
+      &[[0, 1, 3]],
+      Material::Black,
+    );
+  }
+}
@@ -0,0 +1,218 @@
+use std::f64::consts::PI;
+
+use crate::base::math::{get_uniform_random, RandGen};
+use crate::base::vec::Vec3;
+
+// 粗さが0に近いと分布関数が発散するので、これより小さくしない
+const MIN_ALPHA: f64 = 1.0e-3;
+
+// GGX マイクロファセットの金属/粗さモデル
+// 方向はすべて法線を +Z とした局所座標で、v は視点側、l は光源側を向く単位ベクトル
+#[derive(Debug, Clone, Copy)]
+pub struct MicrofacetBsdf {
+  base_color: Vec3,
+  metallic: f64,
+  roughness: f64,
+  // 非金属の垂直入射での反射率を 0.08 * specular とする(0.5 で 4%)
+  specular: f64,
+}
+
+impl MicrofacetBsdf {
+  pub fn new(base_color: Vec3, metallic: f64, roughness: f64, specular: f64) -> Self {
+    Self {
+      base_color,
+      metallic: metallic.clamp(0.0, 1.0),
+      roughness: roughness.clamp(0.0, 1.0),
+      specular: specular.max(0.0),
+    }
+  }
+
+  // 見た目の粗さを2乗したものを GGX の α として使う
+  fn alpha(&self) -> f64 {
+    (self.roughness * self.roughness).max(MIN_ALPHA)
+  }
+
+  fn f0(&self) -> Vec3 {
+    let dielectric = Vec3::from_one((0.08 * self.specular).min(1.0));
+    Vec3::lerp(self.metallic, &dielectric, &self.base_color)
+  }
+
+  fn fresnel(&self, cos: f64) -> Vec3 {
+    let f0 = self.f0();
+    f0 + (Vec3::from_one(1.0) - f0) * (1.0 - cos).clamp(0.0, 1.0).powi(5)
+  }
+
+  fn distribution(&self, cos_h: f64) -> f64 {
+    let a2 = self.alpha() * self.alpha();
+    let d = cos_h * cos_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+  }
+
+  // Smith のマスキング関数の Λ
+  fn lambda(&self, w: &Vec3) -> f64 {
+    let cos2 = w.get_z() * w.get_z();
+    let tan2 = (1.0 - cos2).max(0.0) / cos2;
+    ((1.0 + self.alpha() * self.alpha() * tan2).sqrt() - 1.0) / 2.0
+  }
+
+  // 鏡面反射と拡散反射のどちらを選ぶかの確率。v から見た両者の明るさの比にする
+  fn specular_probability(&self, v: &Vec3) -> f64 {
+    let luminance = |c: Vec3| 0.2126 * c.get_x() + 0.7152 * c.get_y() + 0.0722 * c.get_z();
+    let f = self.fresnel(v.get_z());
+    let specular = luminance(f);
+    let diffuse = luminance((Vec3::from_one(1.0) - f) * self.base_color) * (1.0 - self.metallic);
+    if specular + diffuse <= 0.0 {
+      return 1.0;
+    }
+    specular / (specular + diffuse)
+  }
+
+  // BSDF に cosθl を掛けたもの
+  pub fn eval(&self, v: &Vec3, l: &Vec3) -> Vec3 {
+    if v.get_z() <= 0.0 || l.get_z() <= 0.0 {
+      return Vec3::zero_vector();
+    }
+    let h = (*v + *l).normalize();
+    let f = self.fresnel(v.dot(&h));
+    let g = 1.0 / (1.0 + self.lambda(v) + self.lambda(l));
+    let specular = f * (self.distribution(h.get_z()) * g / (4.0 * v.get_z() * l.get_z()));
+    // 鏡面反射で返らなかった分だけが拡散反射に回る。視線の角度でのフレネル反射率で分ける
+    let diffuse = (Vec3::from_one(1.0) - self.fresnel(v.get_z()))
+      * self.base_color
+      * ((1.0 - self.metallic) / PI);
+    (specular + diffuse) * l.get_z()
+  }
+
+  // sample が l を選ぶ確率密度(立体角あたり)
+  pub fn pdf(&self, v: &Vec3, l: &Vec3) -> f64 {
+    if v.get_z() <= 0.0 || l.get_z() <= 0.0 {
+      return 0.0;
+    }
+    let h = (*v + *l).normalize();
+    let specular = self.distribution(h.get_z()) * h.get_z() / (4.0 * v.dot(&h));
+    let diffuse = l.get_z() / PI;
+    let p = self.specular_probability(v);
+    p * specular + (1.0 - p) * diffuse
+  }
+
+  // 鏡面反射はマイクロファセットの法線を D(h)cosθh に従って選び、拡散反射は cosθ に従って選ぶ
+  // 表面の下に向かう方向を選んだ場合は None
+  pub fn sample(&self, v: &Vec3, rng: &mut RandGen) -> Option<Vec3> {
+    if v.get_z() <= 0.0 {
+      return None;
+    }
+    let l = if get_uniform_random(rng) < self.specular_probability(v) {
+      let a2 = self.alpha() * self.alpha();
+      let r1 = get_uniform_random(rng);
+      let r2 = get_uniform_random(rng);
+      let phi = 2.0 * PI * r1;
+      let cos2 = (1.0 - r2) / (1.0 + (a2 - 1.0) * r2);
+      let sin = (1.0 - cos2).max(0.0).sqrt();
+      let h = Vec3::new(phi.cos() * sin, phi.sin() * sin, cos2.sqrt());
+      h * (2.0 * v.dot(&h)) - *v
+    } else {
+      Vec3::gen_random_cosine_direction(rng)
+    };
+    if l.get_z() <= 0.0 {
+      return None;
+    }
+    Some(l)
+  }
+}
+
+#[cfg(test)]
+mod microfacet_test {
+  use super::*;
+  use crate::base::math::new_rand_gen;
+
+  fn direction(theta_deg: f64) -> Vec3 {
+    let theta = theta_deg.to_radians();
+    Vec3::new(theta.sin(), 0.0, theta.cos())
+  }
+
+  // 白い環境に置いたときに返ってくる光の割合(方向別アルベド)をモンテカルロ法で求める
+  fn albedo(bsdf: &MicrofacetBsdf, v: &Vec3, n: usize) -> Vec3 {
+    let mut rng = new_rand_gen(11);
+    let mut sum = Vec3::zero_vector();
+    for _ in 0..n {
+      if let Some(l) = bsdf.sample(v, &mut rng) {
+        sum = sum + bsdf.eval(v, &l) / bsdf.pdf(v, &l);
+      }
+    }
+    sum / n as f64
+  }
+
+  #[test]
+  fn test_white_furnace() {
+    // どの粗さ、金属度、入射角でも、白い材質が受けた以上の光を返すことはない
+    let white = Vec3::from_one(1.0);
+    for metallic in [0.0, 1.0] {
+      for roughness in [0.05, 0.3, 0.6, 1.0] {
+        let bsdf = MicrofacetBsdf::new(white, metallic, roughness, 0.5);
+        for theta in [0.0, 45.0, 75.0] {
+          let a = albedo(&bsdf, &direction(theta), 20000);
+          assert!(
+            a.get_x() <= 1.02,
+            "{} {} {}: {:?}",
+            metallic,
+            roughness,
+            theta,
+            a
+          );
+          // 粗い面ではマイクロファセット間の多重散乱を無視する分だけ暗くなるが、
+          // 滑らかな面ではほとんど失わない
+          if roughness <= 0.3 {
+            assert!(
+              a.get_x() >= 0.9,
+              "{} {} {}: {:?}",
+              metallic,
+              roughness,
+              theta,
+              a
+            );
+          }
+          assert!(a.get_x() > 0.0);
+          assert_eq!(a.get_x(), a.get_y());
+        }
+      }
+    }
+    // 滑らかな金属はほぼすべての光を返す
+    let mirror = MicrofacetBsdf::new(white, 1.0, 0.05, 0.5);
+    assert!((albedo(&mirror, &direction(30.0), 20000).get_x() - 1.0).abs() < 0.02);
+  }
+
+  #[test]
+  fn test_pdf_matches_sampling() {
+    // pdf を全方向で積分すると、表面の下を選ぶ分を除いて 1 以下になる
+    let bsdf = MicrofacetBsdf::new(Vec3::new(0.8, 0.5, 0.2), 0.3, 0.5, 0.5);
+    let v = direction(40.0);
+    let mut rng = new_rand_gen(5);
+    let n = 200000;
+    let mut sum = 0.0;
+    for _ in 0..n {
+      // 半球上の一様サンプリング(pdf = 1 / 2π)で積分する
+      let z = get_uniform_random(&mut rng);
+      let phi = 2.0 * PI * get_uniform_random(&mut rng);
+      let r = (1.0 - z * z).sqrt();
+      let l = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+      sum += bsdf.pdf(&v, &l) * 2.0 * PI;
+    }
+    let integral = sum / n as f64;
+    assert!((0.9..=1.01).contains(&integral), "{}", integral);
+  }
+
+  #[test]
+  fn test_eval_symmetric() {
+    // 金属の BSDF は v と l を入れ替えても同じ値になる
+    let bsdf = MicrofacetBsdf::new(Vec3::from_one(1.0), 1.0, 0.4, 0.5);
+    let v = direction(20.0);
+    let l = Vec3::new(-0.5, 0.3, 0.8).normalize();
+    let a = bsdf.eval(&v, &l) / l.get_z();
+    let b = bsdf.eval(&l, &v) / v.get_z();
+    assert!((a - b).norm() < 1e-9 * a.norm());
+    assert_eq!(
+      bsdf.eval(&v, &Vec3::new(0.0, 0.0, -1.0)),
+      Vec3::zero_vector()
+    );
+  }
+}
@@ -1,6 +1,16 @@
+pub mod aabb;
+pub mod bvh;
 pub mod camera;
+pub mod cuboid;
 pub mod hit;
 pub mod material;
+pub mod mesh;
+pub mod microfacet;
+pub mod obj_loader;
 pub mod ray;
 pub mod shape;
+pub mod shape_list;
 pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
@@ -0,0 +1,373 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::base::matrix::Mat4;
+use crate::base::vec::Vec3;
+use crate::object::material::Material;
+use crate::object::mesh::{Mesh, MeshData};
+use crate::object::triangle::Triangle;
+
+// 透過する材質に Ni がない(tobj では 1.0 になる)か 1 以下の場合に使う屈折率(ガラス相当)
+// 屈折率 1 では屈折も反射もせず、物体が見えなくなってしまう
+const DEFAULT_REFRACTION_INDEX: f64 = 1.5;
+
+// Wavefront OBJ を読み込み、全体を1つのメッシュにする
+// mtl_path を指定すると、OBJ の mtllib の代わりにそのファイルから材質を読む
+// 材質の指定がない面には default_material を使う
+// 頂点は transform で変換してから保持する(法線は逆行列の転置で変換する)
+// 対応していない MTL の機能は読み飛ばし、警告として返す
+pub fn load_obj(
+  path: &Path,
+  mtl_path: Option<&Path>,
+  name: String,
+  transform: &Mat4,
+  default_material: Material,
+) -> Result<(Mesh, Vec<String>), tobj::LoadError> {
+  let options = tobj::LoadOptions {
+    triangulate: true,
+    single_index: true,
+    ..Default::default()
+  };
+  let (models, materials) = match mtl_path {
+    Some(mtl_path) => {
+      let mut reader = std::io::BufReader::new(
+        std::fs::File::open(path).map_err(|_| tobj::LoadError::OpenFileFailed)?,
+      );
+      tobj::load_obj_buf(&mut reader, &options, |_| tobj::load_mtl(mtl_path))?
+    }
+    None => tobj::load_obj(path, &options)?,
+  };
+
+  let mut warnings = vec![];
+  let materials = match materials {
+    Ok(materials) => materials
+      .iter()
+      .map(|m| Arc::new(convert_material(m, &mut warnings)))
+      .collect(),
+    Err(e) => {
+      warnings.push(format!(
+        "{}: failed to load materials ({}), using the default material",
+        path.display(),
+        e
+      ));
+      vec![]
+    }
+  };
+
+  // 正則でない変換では法線が決まらないので、面の向きから求めさせる
+  let normal_matrix = transform.inverse().map(|m| m.transpose());
+  let default_material = Arc::new(default_material);
+  let mut triangles = vec![];
+  for model in models.iter() {
+    let mesh = &model.mesh;
+    let positions = mesh
+      .positions
+      .chunks(3)
+      .map(|p| transform.transform_point(&Vec3::new(p[0], p[1], p[2])))
+      .collect();
+    let normals = match normal_matrix {
+      Some(normal_matrix) if !mesh.normals.is_empty() => Some(
+        mesh
+          .normals
+          .chunks(3)
+          .map(|n| {
+            normal_matrix
+              .transform_vector(&Vec3::new(n[0], n[1], n[2]))
+              .normalize()
+          })
+          .collect(),
+      ),
+      _ => None,
+    };
+    let material = match mesh.material_id {
+      Some(id) if id < materials.len() => materials[id].clone(),
+      _ => default_material.clone(),
+    };
+    let data = Arc::new(MeshData::new(model.name.clone(), positions, normals));
+    for face in mesh.indices.chunks(3) {
+      triangles.push(Triangle::from_mesh(
+        data.clone(),
+        [face[0] as usize, face[1] as usize, face[2] as usize],
+        material.clone(),
+      ));
+    }
+  }
+  Ok((Mesh::from_triangles(name, triangles), warnings))
+}
+
+// MTL の材質をなるべく近い Material に置き換える
+// Ke があれば光源、透過していれば誘電体、PBR 拡張の Pr/Pm があればマイクロファセット、
+// 鏡面反射が主なら金属、それ以外は拡散反射面とする
+fn convert_material(m: &tobj::Material, warnings: &mut Vec<String>) -> Material {
+  let textures = [
+    ("map_Ka", &m.ambient_texture),
+    ("map_Kd", &m.diffuse_texture),
+    ("map_Ks", &m.specular_texture),
+    ("map_Bump", &m.normal_texture),
+    ("map_Ns", &m.shininess_texture),
+    ("map_d", &m.dissolve_texture),
+  ];
+  for (key, texture) in textures.iter() {
+    if !texture.is_empty() {
+      warnings.push(format!(
+        "material {}: {} ({}) is not supported",
+        m.name, key, texture
+      ));
+    }
+  }
+
+  let mut emission = None;
+  let mut dissolve = m.dissolve;
+  let mut roughness = None;
+  let mut metallic = None;
+  let mut unknown_keys: Vec<&String> = m.unknown_param.keys().collect();
+  unknown_keys.sort();
+  for key in unknown_keys {
+    let value = &m.unknown_param[key];
+    match key.as_str() {
+      "Ke" => emission = parse_vec3(value),
+      "Pr" => roughness = value.trim().parse::<f64>().ok(),
+      "Pm" => metallic = value.trim().parse::<f64>().ok(),
+      "Tr" => {
+        if let Ok(tr) = value.trim().parse::<f64>() {
+          dissolve = 1.0 - tr;
+        }
+      }
+      _ => warnings.push(format!("material {}: {} is not supported", m.name, key)),
+    }
+  }
+
+  let diffuse = Vec3::new(m.diffuse[0], m.diffuse[1], m.diffuse[2]);
+  let specular = Vec3::new(m.specular[0], m.specular[1], m.specular[2]);
+  let max_component = |v: &Vec3| v.get_x().max(v.get_y()).max(v.get_z());
+  let illum = m.illumination_model;
+
+  if let Some(emit) = emission.filter(|e| max_component(e) > 0.0) {
+    Material::DiffuseLight { emit }
+  } else if dissolve < 1.0 || matches!(illum, Some(4) | Some(6) | Some(7) | Some(9)) {
+    let refraction_index = if m.optical_density > 1.0 {
+      m.optical_density
+    } else {
+      warnings.push(format!(
+        "material {}: transparent material without Ni > 1, using refraction index {}",
+        m.name, DEFAULT_REFRACTION_INDEX
+      ));
+      DEFAULT_REFRACTION_INDEX
+    };
+    Material::Dielectric {
+      refraction_index,
+      absorption: Vec3::zero_vector(),
+    }
+  } else if roughness.is_some() || metallic.is_some() {
+    Material::Microfacet {
+      base_color: diffuse.into(),
+      metallic: metallic.unwrap_or(0.0),
+      roughness: roughness.unwrap_or(0.5),
+      specular: 0.5,
+    }
+  } else if matches!(illum, Some(3) | Some(5) | Some(8))
+    || (max_component(&specular) > 0.0 && max_component(&diffuse) == 0.0)
+  {
+    // Phong の鏡面指数 Ns から粗さを見積もる
+    Material::Metal {
+      albedo: specular.into(),
+      fuzzy: (2.0 / (m.shininess + 2.0)).sqrt(),
+    }
+  } else {
+    Material::Lambertian {
+      albedo: diffuse.into(),
+    }
+  }
+}
+
+fn parse_vec3(value: &str) -> Option<Vec3> {
+  let v: Vec<f64> = value
+    .split_whitespace()
+    .filter_map(|s| s.parse().ok())
+    .collect();
+  match v.len() {
+    1 => Some(Vec3::from_one(v[0])),
+    3 => Some(Vec3::new(v[0], v[1], v[2])),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod obj_loader_test {
+  use super::*;
+  use crate::object::ray::Ray;
+  use crate::object::shape::Shape;
+  use std::path::PathBuf;
+
+  const OBJ: &str = "mtllib test.mtl
+o quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vn 0 0 1
+usemtl red
+f 1//1 2//1 3//1 4//1
+o triangle
+v 0 0 2
+v 1 0 2
+v 0 1 2
+usemtl lamp
+f 5 6 7
+o glass
+v 0 0 4
+v 1 0 4
+v 0 1 4
+usemtl glass
+f 8 9 10
+o tinted
+v 0 0 6
+v 1 0 6
+v 0 1 6
+usemtl tinted
+f 11 12 13
+";
+
+  const MTL: &str = "newmtl red
+Kd 0.8 0.1 0.1
+map_Kd red.png
+newmtl lamp
+Kd 0 0 0
+Ke 4 4 4
+newmtl glass
+Ni 1.7
+Tr 0.9
+Ps 0.5
+newmtl tinted
+Kd 0.5 0.5 0.5
+d 0.5
+";
+
+  // テストごとに別のディレクトリへ OBJ と MTL を書き出す
+  fn write_files(dir_name: &str, mtl_name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(dir_name);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("test.obj"), OBJ).unwrap();
+    std::fs::write(dir.join(mtl_name), MTL).unwrap();
+    dir
+  }
+
+  #[test]
+  fn test_load() {
+    let dir = write_files("ray_tracers_obj_loader_test", "test.mtl");
+    let (mesh, warnings) = load_obj(
+      &dir.join("test.obj"),
+      None,
+      "model".to_string(),
+      &Mat4::identity(),
+      Material::Black,
+    )
+    .unwrap();
+    // 四角形は2枚の三角形に分割される
+    assert_eq!(mesh.triangles().len(), 5);
+
+    let ray = Ray::new(Vec3::new(0.8, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+    let hit = mesh.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert_eq!(hit.get_t(), 1.0);
+    assert!(matches!(
+      hit.get_hit_material(),
+      Material::Lambertian { .. }
+    ));
+
+    let ray = Ray::new(Vec3::new(0.2, 0.2, 3.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit = mesh.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert!(matches!(
+      hit.get_hit_material(),
+      Material::DiffuseLight { .. }
+    ));
+
+    let refraction_index = |z: f64| {
+      let ray = Ray::new(Vec3::new(0.2, 0.2, z), Vec3::new(0.0, 0.0, -1.0));
+      match mesh.hit(&ray, 0.001, f64::MAX).unwrap().get_hit_material() {
+        Material::Dielectric {
+          refraction_index, ..
+        } => *refraction_index,
+        m => panic!("expected dielectric: {:?}", m),
+      }
+    };
+    assert_eq!(refraction_index(5.0), 1.7);
+    // Ni のない半透明の材質はガラスの屈折率にして警告する
+    assert_eq!(refraction_index(7.0), DEFAULT_REFRACTION_INDEX);
+
+    assert_eq!(warnings.len(), 3);
+    assert!(warnings[0].contains("map_Kd"));
+    assert!(warnings[1].contains("Ps"));
+    assert!(warnings[2].contains("tinted") && warnings[2].contains("Ni"));
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_load_with_transform() {
+    let dir = write_files("ray_tracers_obj_loader_transform_test", "other.mtl");
+    let transform = Mat4::translation(&Vec3::new(0.0, 0.0, 10.0))
+      * Mat4::rotation(&Vec3::new(0.0, 0.0, 1.0), 90f64.to_radians())
+      * Mat4::scale(&Vec3::from_one(2.0));
+    // mtllib の test.mtl は無いが、other.mtl を明示しているので材質が読める
+    let (mesh, warnings) = load_obj(
+      &dir.join("test.obj"),
+      Some(&dir.join("other.mtl")),
+      "model".to_string(),
+      &transform,
+      Material::Black,
+    )
+    .unwrap();
+    assert_eq!(warnings.len(), 3);
+
+    // 四角形は 2倍されてからZ軸まわりに90度回り、z = 10 に移る
+    let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+    let hit = mesh.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert!((hit.get_t() - 10.0).abs() < 1e-9);
+    assert!((*hit.get_normal() - Vec3::new(0.0, 0.0, -1.0)).norm() < 1e-9);
+    assert!(matches!(
+      hit.get_hit_material(),
+      Material::Lambertian { .. }
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_pbr_material() {
+    let mtl = "newmtl gold
+Kd 1.0 0.8 0.3
+Pm 1.0
+Pr 0.2
+";
+    let (materials, _) = tobj::load_mtl_buf(&mut mtl.as_bytes()).unwrap();
+    let mut warnings = vec![];
+    match convert_material(&materials[0], &mut warnings) {
+      Material::Microfacet {
+        metallic,
+        roughness,
+        ..
+      } => {
+        assert_eq!(metallic, 1.0);
+        assert_eq!(roughness, 0.2);
+      }
+      m => panic!("expected microfacet: {:?}", m),
+    }
+    assert!(warnings.is_empty());
+  }
+
+  #[test]
+  fn test_missing_mtl() {
+    let dir = std::env::temp_dir().join("ray_tracers_obj_loader_missing_mtl_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("test.obj"), OBJ).unwrap();
+    let (mesh, warnings) = load_obj(
+      &dir.join("test.obj"),
+      None,
+      "model".to_string(),
+      &Mat4::identity(),
+      Material::Black,
+    )
+    .unwrap();
+    assert_eq!(mesh.triangles().len(), 5);
+    assert_eq!(warnings.len(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
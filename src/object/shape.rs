@@ -1,11 +1,59 @@
+use crate::base::math::RandGen;
 use crate::base::vec::Vec3;
+use crate::object::aabb::Aabb;
 use crate::object::hit::HitInfo;
 use crate::object::ray::Ray;
 
-pub trait Shape {
-  fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo>;
+// 描画スレッド間で共有するため Send + Sync を要求する
+// 形状は交差判定だけを受け持ち、光の散乱は HitInfo に入れた材質が受け持つ
+pub trait Shape: Send + Sync {
+  fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo<'_>>;
 
-  fn scatter(&self, incoming_ray: &Ray, hit_info: &HitInfo) -> Option<(Ray, Vec3)>;
+  // 物体全体を囲む境界ボックス
+  fn bounding_box(&self) -> Aabb;
 
   fn name(&self) -> &str;
+
+  // 光源として直接サンプリングする物体なら true
+  // true を返す形状は sample_direction と direction_pdf も実装する
+  fn is_light(&self) -> bool {
+    false
+  }
+
+  // origin から物体上の点へ向かう方向を選ぶ
+  fn sample_direction(&self, _origin: &Vec3, _rng: &mut RandGen) -> Option<Vec3> {
+    None
+  }
+
+  // sample_direction が direction を選ぶ確率密度(立体角あたり)
+  fn direction_pdf(&self, _origin: &Vec3, _direction: &Vec3) -> f64 {
+    0.0
+  }
+}
+
+// Box<dyn Shape> の配列もそのままBVHなどに渡せるようにする
+impl Shape for Box<dyn Shape> {
+  fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo<'_>> {
+    self.as_ref().hit(ray, t0, t1)
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    self.as_ref().bounding_box()
+  }
+
+  fn name(&self) -> &str {
+    self.as_ref().name()
+  }
+
+  fn is_light(&self) -> bool {
+    self.as_ref().is_light()
+  }
+
+  fn sample_direction(&self, origin: &Vec3, rng: &mut RandGen) -> Option<Vec3> {
+    self.as_ref().sample_direction(origin, rng)
+  }
+
+  fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+    self.as_ref().direction_pdf(origin, direction)
+  }
 }
@@ -0,0 +1,131 @@
+use crate::object::aabb::Aabb;
+use crate::object::hit::HitInfo;
+use crate::object::ray::Ray;
+use crate::object::shape::Shape;
+
+// 複数の物体をまとめて1つの物体として扱うためのリスト
+pub struct ShapeList {
+  shapes: Vec<Box<dyn Shape>>,
+  name: String,
+}
+
+impl ShapeList {
+  pub fn new(shapes: Vec<Box<dyn Shape>>, name: String) -> Self {
+    Self { shapes, name }
+  }
+
+  pub fn push(&mut self, shape: Box<dyn Shape>) {
+    self.shapes.push(shape);
+  }
+
+  pub fn shapes(&self) -> &[Box<dyn Shape>] {
+    &self.shapes
+  }
+
+  pub fn len(&self) -> usize {
+    self.shapes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.shapes.is_empty()
+  }
+
+  // [t0, t1] の範囲で光線に最も近い物体とその衝突情報を返す
+  // 衝突が見つかるたびに t1 をその位置まで縮めるので、最後に残るのが最も手前の衝突になる
+  pub fn closest_hit(&self, ray: &Ray, t0: f64, mut t1: f64) -> Option<(&dyn Shape, HitInfo<'_>)> {
+    let mut closest = None;
+    for shape in self.shapes.iter() {
+      if let Some(hit_info) = shape.hit(ray, t0, t1) {
+        t1 = hit_info.get_t();
+        closest = Some((shape.as_ref(), hit_info));
+      }
+    }
+    closest
+  }
+}
+
+impl Shape for ShapeList {
+  fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo<'_>> {
+    self.closest_hit(ray, t0, t1).map(|(_, hit_info)| hit_info)
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    self
+      .shapes
+      .iter()
+      .fold(Aabb::empty(), |acc, s| acc.surrounding(&s.bounding_box()))
+  }
+
+  fn name(&self) -> &str {
+    self.name.as_str()
+  }
+}
+
+#[cfg(test)]
+mod shape_list_test {
+  use super::*;
+  use crate::base::vec::Vec3;
+  use crate::object::bvh::Bvh;
+  use crate::object::material::Material;
+  use crate::object::sphere::Sphere;
+
+  fn sphere(x: f64, name: &str) -> Box<dyn Shape> {
+    Box::new(Sphere::new(
+      Vec3::new(x, 0.0, 0.0),
+      1.0,
+      name.to_string(),
+      Material::Black,
+    ))
+  }
+
+  #[test]
+  fn test_closest_hit_near_first() {
+    let list = ShapeList::new(
+      vec![sphere(5.0, "near"), sphere(10.0, "far")],
+      "list".to_string(),
+    );
+    let ray = Ray::new(Vec3::zero_vector(), Vec3::new(1.0, 0.0, 0.0));
+    let (shape, hit_info) = list.closest_hit(&ray, 0.001, f64::MAX).unwrap();
+    assert_eq!(shape.name(), "near");
+    assert_eq!(hit_info.get_t(), 4.0);
+  }
+
+  #[test]
+  fn test_closest_hit_far_first() {
+    let list = ShapeList::new(
+      vec![sphere(10.0, "far"), sphere(5.0, "near")],
+      "list".to_string(),
+    );
+    let ray = Ray::new(Vec3::zero_vector(), Vec3::new(1.0, 0.0, 0.0));
+    let (shape, hit_info) = list.closest_hit(&ray, 0.001, f64::MAX).unwrap();
+    assert_eq!(shape.name(), "near");
+    assert_eq!(hit_info.get_t(), 4.0);
+    assert_eq!(list.hit(&ray, 0.001, f64::MAX).unwrap().get_t(), 4.0);
+  }
+
+  #[test]
+  fn test_closest_hit_in_range() {
+    let list = ShapeList::new(
+      vec![sphere(5.0, "near"), sphere(10.0, "far")],
+      "list".to_string(),
+    );
+    let ray = Ray::new(Vec3::zero_vector(), Vec3::new(1.0, 0.0, 0.0));
+    // 手前の球を範囲外にすると奥の球に当たる
+    let (shape, _) = list.closest_hit(&ray, 6.5, f64::MAX).unwrap();
+    assert_eq!(shape.name(), "far");
+    assert!(list.closest_hit(&ray, 0.001, 3.0).is_none());
+  }
+
+  #[test]
+  fn test_same_result_as_bvh() {
+    for shapes in [
+      vec![sphere(5.0, "near"), sphere(10.0, "far")],
+      vec![sphere(10.0, "far"), sphere(5.0, "near")],
+    ] {
+      let bvh = Bvh::build(&shapes);
+      let ray = Ray::new(Vec3::zero_vector(), Vec3::new(1.0, 0.0, 0.0));
+      let (shape, _) = bvh.hit(&shapes, &ray, 0.001, f64::MAX).unwrap();
+      assert_eq!(shape.name(), "near");
+    }
+  }
+}
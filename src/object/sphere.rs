@@ -1,4 +1,9 @@
+use std::f64::consts::PI;
+
+use crate::base::math::{get_uniform_random, RandGen};
+use crate::base::onb::Onb;
 use crate::base::vec::Vec3;
+use crate::object::aabb::Aabb;
 use crate::object::hit::HitInfo;
 use crate::object::material::Material;
 use crate::object::ray::Ray;
@@ -24,10 +29,35 @@ impl Sphere {
   pub fn center(&self) -> Vec3 {
     self.center
   }
+
+  // origin から見た球の見かけの半径の cos。origin が球の内部にある場合は None
+  fn cos_theta_max(&self, origin: &Vec3) -> Option<f64> {
+    let to_center = self.center - *origin;
+    let distance2 = to_center.dot(&to_center);
+    let radius2 = self.radius * self.radius;
+    if distance2 <= radius2 {
+      return None;
+    }
+    Some((1.0 - radius2 / distance2).sqrt())
+  }
+
+  // 単位球面上の点 p の UV 座標
+  // u は +X 方向から Z 軸まわりに反時計回りに一周、v は南極(-Z)から北極(+Z)まで
+  fn spherical_uv(p: &Vec3) -> (f64, f64) {
+    let theta = (-p.get_z()).clamp(-1.0, 1.0).acos();
+    let phi = p
+      .get_y()
+      .atan2(p.get_x())
+      .rem_euclid(2.0 * std::f64::consts::PI);
+    (
+      phi / (2.0 * std::f64::consts::PI),
+      theta / std::f64::consts::PI,
+    )
+  }
 }
 
 impl Shape for Sphere {
-  fn hit(&self, ray: &Ray, lower_range: f64, upper_range: f64) -> Option<HitInfo> {
+  fn hit(&self, ray: &Ray, lower_range: f64, upper_range: f64) -> Option<HitInfo<'_>> {
     let v_oc = ray.origin() - &self.center();
     let a = ray.direction().norm() * ray.direction().norm();
     let half_b = v_oc.dot(&ray.direction());
@@ -49,62 +79,53 @@ impl Shape for Sphere {
         temp,
         pos.clone(),
         (pos.clone() - self.center()) / self.radius,
-        self.material,
+        &self.material,
         &ray,
       );
-      hit_info.set_front_face(&ray, &((pos.clone() - self.center()) / self.radius));
-      return Some(hit_info);
+      let outward_normal = (pos.clone() - self.center()) / self.radius;
+      hit_info.set_front_face(&ray, &outward_normal);
+      // 半径が負の球でも模様の向きが変わらないように、中心からの向きで求める
+      let (u, v) = Self::spherical_uv(&(pos - self.center()).normalize());
+      return Some(hit_info.with_uv(u, v));
     }
     None
   }
 
-  fn scatter(&self, incoming_ray: &Ray, hit_info: &HitInfo) -> Option<(Ray, Vec3)> {
-    match hit_info.get_hit_material() {
-      Material::Lambertian { albedo } => {
-        //let mut scatter_dir =
-        //  hit_info.get_normal().clone() + Vec3::gen_random_vector_in_unit_shpere().normalize();
-        let mut scatter_dir = Vec3::gen_random_vector_in_unit_shpere().normalize();
-        if scatter_dir.near_zero() {
-          scatter_dir = hit_info.get_normal().clone();
-        }
-
-        let scattered = Ray::new(hit_info.get_poisition().clone(), scatter_dir);
-        return Some((scattered, albedo.clone()));
-      }
-      Material::Metal { albedo, fuzzy } => {
-        let reflected = Vec3::reflect(
-          &incoming_ray.direction().normalize(),
-          &hit_info.get_normal(),
-        ) + Vec3::gen_random_vector_in_unit_shpere() * fuzzy.clamp(0.0, 1.0);
-        let scatterd = Ray::new(hit_info.get_poisition().clone(), reflected);
-        if scatterd.direction().dot(hit_info.get_normal()) > 0.0 {
-          return Some((scatterd, albedo.clone()));
-        }
-        None
-      }
-      Material::Dielectric { refraction_index } => {
-        let attenuation = Vec3::from_one(1.0);
-        let refraction_ratio = if hit_info.front_face() {
-          1.0 / refraction_index
-        } else {
-          *refraction_index
-        };
-
-        let unit_dir = incoming_ray.direction().normalize();
-        let refracted = Vec3::refract(&unit_dir, &hit_info.get_normal(), refraction_ratio);
-        let scatterd = Ray::new(hit_info.get_poisition().clone(), refracted);
-        return Some((scatterd, attenuation));
-      }
-      _ => Some((
-        Ray::new(Vec3::zero_vector(), Vec3::zero_vector()),
-        Vec3::zero_vector(),
-      )),
-    }
+  fn bounding_box(&self) -> Aabb {
+    let r = Vec3::from_one(self.radius.abs());
+    Aabb::new(self.center - r, self.center + r)
   }
 
   fn name(&self) -> &str {
     self.name.as_str()
   }
+
+  fn is_light(&self) -> bool {
+    self.material.is_emissive()
+  }
+
+  // origin から見た球の範囲(中心方向を軸とする円錐)の中で一様に方向を選ぶ
+  fn sample_direction(&self, origin: &Vec3, rng: &mut RandGen) -> Option<Vec3> {
+    let cos_max = self.cos_theta_max(origin)?;
+    let z = 1.0 + get_uniform_random(rng) * (cos_max - 1.0);
+    let phi = 2.0 * PI * get_uniform_random(rng);
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let onb = Onb::from_w(&(self.center - *origin));
+    Some(onb.local(&Vec3::new(r * phi.cos(), r * phi.sin(), z)))
+  }
+
+  fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+    let Some(cos_max) = self.cos_theta_max(origin) else {
+      return 0.0;
+    };
+    if self
+      .hit(&Ray::new(*origin, *direction), 0.001, f64::MAX)
+      .is_none()
+    {
+      return 0.0;
+    }
+    1.0 / (2.0 * PI * (1.0 - cos_max))
+  }
 }
 
 #[cfg(test)]
@@ -124,4 +145,54 @@ mod sphere_test {
     assert_eq!(sphere.hit(&r1, 0.0, std::f64::MAX).is_some(), true);
     assert_eq!(sphere.hit(&r2, 0.0, std::f64::MAX).is_none(), true);
   }
+
+  #[test]
+  fn test_uv() {
+    let sphere = Sphere::new(
+      Vec3::new(0.0, 0.0, 5.0),
+      1.0,
+      "sample".to_string(),
+      Material::Black,
+    );
+    let top = Ray::new(Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+    let (_, v) = sphere.hit(&top, 0.0, f64::MAX).unwrap().get_uv();
+    assert!((v - 1.0).abs() < 1e-9);
+
+    let side = Ray::new(Vec3::new(0.0, -10.0, 5.0), Vec3::new(0.0, 1.0, 0.0));
+    let (u, v) = sphere.hit(&side, 0.0, f64::MAX).unwrap().get_uv();
+    assert!((u - 0.75).abs() < 1e-9);
+    assert!((v - 0.5).abs() < 1e-9);
+  }
+
+  #[test]
+  fn test_sample_direction() {
+    let light = Sphere::new(
+      Vec3::new(1.0, 2.0, 3.0),
+      0.5,
+      "light".to_string(),
+      Material::DiffuseLight {
+        emit: Vec3::from_one(1.0),
+      },
+    );
+    assert!(light.is_light());
+    let origin = Vec3::zero_vector();
+    let mut rng = crate::base::math::new_rand_gen(1);
+    // 選んだ方向はすべて球に当たる
+    for _ in 0..1000 {
+      let dir = light.sample_direction(&origin, &mut rng).unwrap();
+      assert!(light.direction_pdf(&origin, &dir) > 0.0);
+    }
+    // 全方向で pdf を積分すると 1 になる
+    let n = 400000;
+    let mut sum = 0.0;
+    for _ in 0..n {
+      let dir = Vec3::gen_random_vector_in_unit_shpere(&mut rng);
+      sum += light.direction_pdf(&origin, &dir) * 4.0 * PI;
+    }
+    assert!((sum / n as f64 - 1.0).abs() < 0.05, "{}", sum / n as f64);
+    // 内部からはサンプリングしない
+    assert!(light
+      .sample_direction(&Vec3::new(1.0, 2.0, 3.1), &mut rng)
+      .is_none());
+  }
 }
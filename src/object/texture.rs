@@ -0,0 +1,270 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use image::{ImageResult, RgbImage};
+
+use crate::base::color::srgb_decode;
+use crate::base::perlin::Perlin;
+use crate::base::vec::Vec3;
+
+// 衝突点の UV 座標と位置から色を決める
+#[derive(Debug, Clone)]
+pub enum Texture {
+  // 一様な色
+  Constant(Vec3),
+  // 空間を一辺 scale の立方体に区切った市松模様
+  Checker {
+    even: Box<Texture>,
+    odd: Box<Texture>,
+    scale: f64,
+  },
+  // 画像を UV 座標で貼り付ける。同じ画像は複数の材質で共有する
+  Image(Arc<ImageTexture>),
+  // 衝突位置から計算するノイズ模様。UV を持たない形状にも使える
+  Noise {
+    perlin: Arc<Perlin>,
+    pattern: NoisePattern,
+    // 位置に掛ける倍率。大きいほど模様が細かくなる
+    scale: f64,
+    ramp: ColorRamp,
+  },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum NoisePattern {
+  Perlin,
+  Turbulence { octaves: usize },
+  // Z 方向の縞を乱流でゆがめた大理石
+  Marble { octaves: usize, turbulence: f64 },
+  // Z 軸を中心とした年輪を乱流でゆがめた木目
+  Wood { octaves: usize, turbulence: f64 },
+}
+
+impl NoisePattern {
+  // 0 から 1 の値を返し、これを色に変換する
+  fn value(&self, perlin: &Perlin, p: &Vec3) -> f64 {
+    let v = match self {
+      NoisePattern::Perlin => 0.5 * (1.0 + perlin.noise(p)),
+      NoisePattern::Turbulence { octaves } => perlin.turbulence(p, *octaves),
+      NoisePattern::Marble {
+        octaves,
+        turbulence,
+      } => 0.5 * (1.0 + (p.get_z() + turbulence * perlin.turbulence(p, *octaves)).sin()),
+      NoisePattern::Wood {
+        octaves,
+        turbulence,
+      } => {
+        let r = p.get_x().hypot(p.get_y()) + turbulence * perlin.turbulence(p, *octaves);
+        r - r.floor()
+      }
+    };
+    v.clamp(0.0, 1.0)
+  }
+}
+
+// 0 から 1 の値を、位置と色の組を線形に補間して色にする
+#[derive(Debug, Clone)]
+pub struct ColorRamp {
+  stops: Vec<(f64, Vec3)>,
+}
+
+impl ColorRamp {
+  pub fn new(mut stops: Vec<(f64, Vec3)>) -> Self {
+    assert!(!stops.is_empty(), "color ramp needs at least one stop");
+    stops.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("color ramp position is NaN"));
+    Self { stops }
+  }
+
+  // 黒から白
+  pub fn grayscale() -> Self {
+    Self::new(vec![(0.0, Vec3::zero_vector()), (1.0, Vec3::from_one(1.0))])
+  }
+
+  pub fn color(&self, t: f64) -> Vec3 {
+    let first = self.stops[0];
+    if t <= first.0 {
+      return first.1;
+    }
+    for pair in self.stops.windows(2) {
+      let (t0, c0) = pair[0];
+      let (t1, c1) = pair[1];
+      if t <= t1 {
+        let s = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1.0 };
+        return Vec3::lerp(s, &c0, &c1);
+      }
+    }
+    self.stops[self.stops.len() - 1].1
+  }
+}
+
+impl Texture {
+  pub fn value(&self, u: f64, v: f64, position: &Vec3) -> Vec3 {
+    match self {
+      Texture::Constant(color) => *color,
+      Texture::Checker { even, odd, scale } => {
+        let cell = |x: f64| (x / scale).floor() as i64;
+        let sum = cell(position.get_x()) + cell(position.get_y()) + cell(position.get_z());
+        if sum.rem_euclid(2) == 0 {
+          even.value(u, v, position)
+        } else {
+          odd.value(u, v, position)
+        }
+      }
+      Texture::Image(image) => image.value(u, v),
+      Texture::Noise {
+        perlin,
+        pattern,
+        scale,
+        ramp,
+      } => ramp.color(pattern.value(perlin, &(*position * *scale))),
+    }
+  }
+}
+
+impl From<Vec3> for Texture {
+  fn from(color: Vec3) -> Self {
+    Texture::Constant(color)
+  }
+}
+
+// PNG や JPEG などの画像テクスチャ
+pub struct ImageTexture {
+  image: RgbImage,
+}
+
+impl ImageTexture {
+  pub fn new(image: RgbImage) -> Self {
+    Self { image }
+  }
+
+  pub fn load(path: &Path) -> ImageResult<Self> {
+    Ok(Self::new(image::open(path)?.into_rgb8()))
+  }
+
+  // u は右向き、v は上向きに 0 から 1。範囲外は端の画素を使う
+  // 画素値は sRGB で符号化されているので、線形の値に戻して使う
+  pub fn value(&self, u: f64, v: f64) -> Vec3 {
+    let (width, height) = self.image.dimensions();
+    if width == 0 || height == 0 {
+      return Vec3::zero_vector();
+    }
+    let x = ((u.clamp(0.0, 1.0) * width as f64) as u32).min(width - 1);
+    let y = (((1.0 - v.clamp(0.0, 1.0)) * height as f64) as u32).min(height - 1);
+    let p = self.image.get_pixel(x, y);
+    let to_linear = |c: u8| srgb_decode(c as f64 / 255.0);
+    Vec3::new(to_linear(p[0]), to_linear(p[1]), to_linear(p[2]))
+  }
+}
+
+impl std::fmt::Debug for ImageTexture {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ImageTexture")
+      .field("dimensions", &self.image.dimensions())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod texture_test {
+  use super::*;
+  use image::Rgb;
+
+  #[test]
+  fn test_checker() {
+    let checker = Texture::Checker {
+      even: Box::new(Texture::Constant(Vec3::from_one(1.0))),
+      odd: Box::new(Texture::Constant(Vec3::zero_vector())),
+      scale: 0.5,
+    };
+    let white = checker.value(0.0, 0.0, &Vec3::new(0.1, 0.1, 0.1));
+    let black = checker.value(0.0, 0.0, &Vec3::new(0.6, 0.1, 0.1));
+    let negative = checker.value(0.0, 0.0, &Vec3::new(-0.1, 0.1, 0.1));
+    assert_eq!(white, Vec3::from_one(1.0));
+    assert_eq!(black, Vec3::zero_vector());
+    assert_eq!(negative, Vec3::zero_vector());
+  }
+
+  #[test]
+  fn test_color_ramp() {
+    let ramp = ColorRamp::new(vec![
+      (1.0, Vec3::new(0.0, 0.0, 1.0)),
+      (0.0, Vec3::new(1.0, 0.0, 0.0)),
+      (0.5, Vec3::new(0.0, 1.0, 0.0)),
+    ]);
+    assert_eq!(ramp.color(-1.0), Vec3::new(1.0, 0.0, 0.0));
+    assert_eq!(ramp.color(0.25), Vec3::new(0.5, 0.5, 0.0));
+    assert_eq!(ramp.color(0.5), Vec3::new(0.0, 1.0, 0.0));
+    assert_eq!(ramp.color(2.0), Vec3::new(0.0, 0.0, 1.0));
+  }
+
+  #[test]
+  fn test_noise() {
+    let perlin = Arc::new(Perlin::new(0));
+    let patterns = [
+      NoisePattern::Perlin,
+      NoisePattern::Turbulence { octaves: 7 },
+      NoisePattern::Marble {
+        octaves: 7,
+        turbulence: 10.0,
+      },
+      NoisePattern::Wood {
+        octaves: 3,
+        turbulence: 0.5,
+      },
+    ];
+    let ramp = ColorRamp::new(vec![
+      (0.0, Vec3::new(0.2, 0.1, 0.0)),
+      (1.0, Vec3::new(0.8, 0.6, 0.4)),
+    ]);
+    for pattern in patterns.iter() {
+      let texture = Texture::Noise {
+        perlin: perlin.clone(),
+        pattern: *pattern,
+        scale: 4.0,
+        ramp: ramp.clone(),
+      };
+      // UV によらず位置だけで決まり、色は ramp の範囲に収まる
+      let p = Vec3::new(0.3, -1.2, 0.7);
+      assert_eq!(texture.value(0.0, 0.0, &p), texture.value(0.9, 0.1, &p));
+      for n in 0..100 {
+        let c = texture.value(0.0, 0.0, &Vec3::new(n as f64 * 0.1, 0.3, -0.2));
+        assert!(c.get_x() >= 0.2 - 1e-9 && c.get_x() <= 0.8 + 1e-9);
+        assert!(c.get_z() >= -1e-9 && c.get_z() <= 0.4 + 1e-9);
+      }
+    }
+  }
+
+  #[test]
+  fn test_image() {
+    // 左上が赤、右上が緑、下段が白の 2x2 画像
+    let image = RgbImage::from_fn(2, 2, |x, y| match (x, y) {
+      (0, 0) => Rgb([255, 0, 0]),
+      (1, 0) => Rgb([0, 255, 0]),
+      _ => Rgb([255, 255, 255]),
+    });
+    let texture = ImageTexture::new(image);
+    assert_eq!(texture.value(0.25, 0.75), Vec3::new(1.0, 0.0, 0.0));
+    assert_eq!(texture.value(1.0, 1.0), Vec3::new(0.0, 1.0, 0.0));
+    assert_eq!(texture.value(0.5, 0.0), Vec3::from_one(1.0));
+    // 中間の値は sRGB の曲線で線形に戻す(188 は約 0.5)
+    let gray = ImageTexture::new(RgbImage::from_pixel(1, 1, Rgb([188, 10, 0])));
+    let value = gray.value(0.5, 0.5);
+    assert!((value.get_x() - 0.5029).abs() < 1e-3);
+    // 暗部は線形
+    assert!((value.get_y() - 10.0 / 255.0 / 12.92).abs() < 1e-12);
+  }
+
+  #[test]
+  fn test_load_png() {
+    let path = std::env::temp_dir().join("ray_tracers_texture_test.png");
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255]))
+      .save(&path)
+      .unwrap();
+    let texture = Texture::Image(Arc::new(ImageTexture::load(&path).unwrap()));
+    assert_eq!(
+      texture.value(0.5, 0.5, &Vec3::zero_vector()),
+      Vec3::new(0.0, 0.0, 1.0)
+    );
+    std::fs::remove_file(&path).unwrap();
+  }
+}
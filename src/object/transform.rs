@@ -0,0 +1,240 @@
+use crate::base::math::RandGen;
+use crate::base::matrix::Mat4;
+use crate::base::vec::Vec3;
+use crate::object::aabb::Aabb;
+use crate::object::hit::HitInfo;
+use crate::object::ray::Ray;
+use crate::object::shape::Shape;
+
+// 物体に座標変換を掛けて配置する
+// 光線を物体の座標系に戻して判定し、結果の位置と法線をワールド座標に移す
+pub struct Transformed {
+  shape: Box<dyn Shape>,
+  // 物体座標からワールド座標への変換
+  to_world: Mat4,
+  // ワールド座標から物体座標への変換(to_world の逆行列)
+  to_object: Mat4,
+  bbox: Aabb,
+}
+
+impl Transformed {
+  pub fn new(shape: Box<dyn Shape>, to_world: Mat4) -> Self {
+    let to_object = to_world
+      .inverse()
+      .unwrap_or_else(|| panic!("transform of {} is not invertible", shape.name()));
+    // 元の境界ボックスの8頂点を変換し、それらを囲む箱を作る
+    let inner = shape.bounding_box();
+    let mut bbox = Aabb::empty();
+    for i in 0..8 {
+      let corner = Vec3::new(
+        if i & 1 == 0 {
+          inner.min().get_x()
+        } else {
+          inner.max().get_x()
+        },
+        if i & 2 == 0 {
+          inner.min().get_y()
+        } else {
+          inner.max().get_y()
+        },
+        if i & 4 == 0 {
+          inner.min().get_z()
+        } else {
+          inner.max().get_z()
+        },
+      );
+      bbox = bbox.expand(&to_world.transform_point(&corner));
+    }
+    Self {
+      shape,
+      to_world,
+      to_object,
+      bbox,
+    }
+  }
+
+  pub fn to_world(&self) -> &Mat4 {
+    &self.to_world
+  }
+}
+
+impl Shape for Transformed {
+  fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo<'_>> {
+    // 方向ベクトルを正規化しないので、t はワールド座標と物体座標で共通になる
+    let object_ray = Ray::new(
+      self.to_object.transform_point(ray.origin()),
+      self.to_object.transform_vector(ray.direction()),
+    );
+    let hit_info = self.shape.hit(&object_ray, t0, t1)?;
+    let position = ray.at(hit_info.get_t());
+    // 法線は逆行列の転置で変換する
+    let normal = self
+      .to_object
+      .transpose()
+      .transform_vector(hit_info.get_normal())
+      .normalize();
+    Some(hit_info.with_position_normal(position, normal))
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    self.bbox
+  }
+
+  fn name(&self) -> &str {
+    self.shape.name()
+  }
+
+  fn is_light(&self) -> bool {
+    self.shape.is_light()
+  }
+
+  // 物体座標で方向を選び、ワールド座標の方向に移す
+  fn sample_direction(&self, origin: &Vec3, rng: &mut RandGen) -> Option<Vec3> {
+    let object_origin = self.to_object.transform_point(origin);
+    let direction = self.shape.sample_direction(&object_origin, rng)?;
+    Some(self.to_world.transform_vector(&direction).normalize())
+  }
+
+  // 物体座標での確率密度に、方向を移したことによる立体角の伸び縮み(ヤコビアン)を掛ける
+  // 単位ベクトル w を線形変換 M で移すと、立体角は |det M| / |M w|^3 倍になる
+  fn direction_pdf(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+    let object_origin = self.to_object.transform_point(origin);
+    let object_direction = self.to_object.transform_vector(&direction.normalize());
+    let length = object_direction.norm();
+    if length <= 0.0 {
+      return 0.0;
+    }
+    let pdf = self
+      .shape
+      .direction_pdf(&object_origin, &(object_direction / length));
+    pdf * self.to_object.linear_determinant().abs() / (length * length * length)
+  }
+}
+
+#[cfg(test)]
+mod transform_test {
+  use super::*;
+  use crate::object::cuboid::Cuboid;
+  use crate::object::material::Material;
+  use crate::object::sphere::Sphere;
+
+  fn assert_near(a: &Vec3, b: &Vec3) {
+    assert!((*a - *b).norm() < 1e-9, "{:?} != {:?}", a, b);
+  }
+
+  #[test]
+  fn test_translate() {
+    let sphere = Sphere::new(
+      Vec3::zero_vector(),
+      1.0,
+      "sphere".to_string(),
+      Material::Black,
+    );
+    let shape = Transformed::new(
+      Box::new(sphere),
+      Mat4::translation(&Vec3::new(0.0, 0.0, 5.0)),
+    );
+    let ray = Ray::new(Vec3::zero_vector(), Vec3::new(0.0, 0.0, 1.0));
+    let hit = shape.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert_eq!(hit.get_t(), 4.0);
+    assert_eq!(*hit.get_poisition(), Vec3::new(0.0, 0.0, 4.0));
+    assert_eq!(*hit.get_normal(), Vec3::new(0.0, 0.0, -1.0));
+    assert!(hit.front_face());
+    assert_eq!(*shape.bounding_box().min(), Vec3::new(-1.0, -1.0, 4.0));
+  }
+
+  #[test]
+  fn test_non_uniform_scale() {
+    // Z方向に3倍した球(楕円体)の斜めの面の法線
+    let sphere = Sphere::new(
+      Vec3::zero_vector(),
+      1.0,
+      "ellipsoid".to_string(),
+      Material::Black,
+    );
+    let shape = Transformed::new(Box::new(sphere), Mat4::scale(&Vec3::new(1.0, 1.0, 3.0)));
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit = shape.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert!((hit.get_t() - 7.0).abs() < 1e-9);
+
+    let s = 0.5_f64.sqrt();
+    let p = Vec3::new(s, 0.0, 3.0 * s);
+    let ray = Ray::new(p * 2.0, p * -1.0);
+    let hit = shape.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert_near(hit.get_poisition(), &p);
+    // 楕円体 x^2 + (z/3)^2 = 1 の勾配方向 (x, z/9)
+    assert_near(hit.get_normal(), &Vec3::new(s, 0.0, s / 3.0).normalize());
+  }
+
+  #[test]
+  fn test_rotate() {
+    let cuboid = Cuboid::new(
+      Vec3::new(-1.0, -1.0, -1.0),
+      Vec3::new(1.0, 1.0, 1.0),
+      "cuboid".to_string(),
+      Material::Black,
+    );
+    let rotation = Mat4::rotation(&Vec3::new(0.0, 0.0, 1.0), 45.0_f64.to_radians());
+    let shape = Transformed::new(Box::new(cuboid), rotation);
+    // 45度回した立方体の角は x = sqrt(2) にある
+    let ray = Ray::new(Vec3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+    let hit = shape.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert!((hit.get_t() - (5.0 - 2.0_f64.sqrt())).abs() < 1e-9);
+    let bbox = shape.bounding_box();
+    assert!((bbox.max().get_x() - 2.0_f64.sqrt()).abs() < 1e-9);
+    assert!((bbox.max().get_z() - 1.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn test_light_sampling() {
+    // 引き延ばして移動した光る球の方向の確率密度は、立体角全体で積分すると 1 になる
+    let sphere = Sphere::new(
+      Vec3::zero_vector(),
+      1.0,
+      "light".to_string(),
+      Material::DiffuseLight {
+        emit: Vec3::from_one(1.0),
+      },
+    );
+    let shape = Transformed::new(
+      Box::new(sphere),
+      Mat4::translation(&Vec3::new(0.0, 0.0, 4.0)) * Mat4::scale(&Vec3::new(2.0, 1.0, 0.5)),
+    );
+    assert!(shape.is_light());
+    let origin = Vec3::zero_vector();
+    let mut rng = crate::base::math::new_rand_gen(1);
+    // 選んだ方向は必ず物体に当たる
+    for _ in 0..100 {
+      let dir = shape.sample_direction(&origin, &mut rng).unwrap();
+      assert!((dir.norm() - 1.0).abs() < 1e-9);
+      assert!(shape.hit(&Ray::new(origin, dir), 0.001, f64::MAX).is_some());
+      assert!(shape.direction_pdf(&origin, &dir) > 0.0);
+    }
+    // 一様な方向で pdf の期待値をとると 1 / (4π) になる
+    let n = 200000;
+    let sum: f64 = (0..n)
+      .map(|_| {
+        let dir = Vec3::gen_random_vector_in_unit_shpere(&mut rng).normalize();
+        shape.direction_pdf(&origin, &dir)
+      })
+      .sum();
+    let integral = sum / n as f64 * 4.0 * std::f64::consts::PI;
+    assert!((integral - 1.0).abs() < 0.05, "{}", integral);
+  }
+
+  #[test]
+  fn test_from_inside() {
+    let sphere = Sphere::new(
+      Vec3::zero_vector(),
+      1.0,
+      "sphere".to_string(),
+      Material::Black,
+    );
+    let shape = Transformed::new(Box::new(sphere), Mat4::scale(&Vec3::from_one(2.0)));
+    let ray = Ray::new(Vec3::zero_vector(), Vec3::new(1.0, 0.0, 0.0));
+    let hit = shape.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert_eq!(hit.get_t(), 2.0);
+    assert!(!hit.front_face());
+    assert_near(hit.get_normal(), &Vec3::new(-1.0, 0.0, 0.0));
+  }
+}
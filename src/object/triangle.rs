@@ -0,0 +1,240 @@
+use std::sync::Arc;
+
+use crate::base::vec::Vec3;
+use crate::object::aabb::Aabb;
+use crate::object::hit::HitInfo;
+use crate::object::material::Material;
+use crate::object::mesh::MeshData;
+use crate::object::ray::Ray;
+use crate::object::shape::Shape;
+
+// 境界ボックスの厚みが0にならないように足す幅
+const AABB_PADDING: f64 = 1.0e-4;
+// 光線と面がほぼ平行とみなす、光線の向きと面の法線がなす角の余弦
+// 行列式は |e1 x e2| * |光線の向き| * 余弦 なので、三角形の大きさや光線の長さによらず判定できる
+const PARALLEL_EPSILON: f64 = 1.0e-12;
+
+// 三角形。頂点はメッシュの頂点配列を共有し、添字だけを持つ
+pub struct Triangle {
+  mesh: Arc<MeshData>,
+  indices: [usize; 3],
+  // メッシュの三角形は同じ材質を共有する
+  material: Arc<Material>,
+}
+
+impl Triangle {
+  // 単独の三角形。normals を指定すると頂点法線を補間して滑らかに見せる
+  pub fn new(
+    vertices: [Vec3; 3],
+    normals: Option<[Vec3; 3]>,
+    name: String,
+    material: Material,
+  ) -> Self {
+    let mesh = MeshData::new(name, vertices.to_vec(), normals.map(|n| n.to_vec()));
+    Self::from_mesh(Arc::new(mesh), [0, 1, 2], Arc::new(material))
+  }
+
+  // メッシュの indices 番目の頂点からなる三角形
+  pub fn from_mesh(mesh: Arc<MeshData>, indices: [usize; 3], material: Arc<Material>) -> Self {
+    for i in indices.iter() {
+      assert!(
+        *i < mesh.positions().len(),
+        "vertex index {} out of range in mesh {}",
+        i,
+        mesh.name()
+      );
+    }
+    Self {
+      mesh,
+      indices,
+      material,
+    }
+  }
+
+  pub fn vertex(&self, k: usize) -> Vec3 {
+    self.mesh.positions()[self.indices[k]]
+  }
+
+  // 面の向き(頂点が反時計回りに見える側)の単位法線
+  pub fn geometric_normal(&self) -> Vec3 {
+    let e1 = self.vertex(1) - self.vertex(0);
+    let e2 = self.vertex(2) - self.vertex(0);
+    e1.cross(&e2).normalize()
+  }
+
+  // Möller–Trumbore 法で交点を求め、(t, u, v) を返す
+  // u, v は頂点1, 2の重心座標で、頂点0の重みは 1 - u - v
+  pub fn intersect(&self, ray: &Ray) -> Option<(f64, f64, f64)> {
+    let p0 = self.vertex(0);
+    let e1 = self.vertex(1) - p0;
+    let e2 = self.vertex(2) - p0;
+    let pvec = ray.direction().cross(&e2);
+    let det = e1.dot(&pvec);
+    // 面積 0 の三角形では右辺も 0 になるので、ここで外れる
+    if det.abs() <= PARALLEL_EPSILON * e1.cross(&e2).norm() * ray.direction().norm() {
+      return None;
+    }
+    let inv_det = 1.0 / det;
+    let tvec = ray.origin() - &p0;
+    let u = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+      return None;
+    }
+    let qvec = tvec.cross(&e1);
+    let v = ray.direction().dot(&qvec) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+      return None;
+    }
+    Some((e2.dot(&qvec) * inv_det, u, v))
+  }
+
+  // 重心座標 (u, v) での法線。頂点法線があれば補間し、面の表側にそろえる
+  fn shading_normal(&self, u: f64, v: f64) -> Vec3 {
+    let geometric = self.geometric_normal();
+    match self.mesh.normals() {
+      Some(normals) => {
+        let n = normals[self.indices[0]] * (1.0 - u - v)
+          + normals[self.indices[1]] * u
+          + normals[self.indices[2]] * v;
+        if n.near_zero() {
+          return geometric;
+        }
+        let n = n.normalize();
+        if n.dot(&geometric) < 0.0 {
+          n * -1.0
+        } else {
+          n
+        }
+      }
+      None => geometric,
+    }
+  }
+}
+
+impl Shape for Triangle {
+  fn hit(&self, ray: &Ray, t0: f64, t1: f64) -> Option<HitInfo<'_>> {
+    let (t, u, v) = self.intersect(ray)?;
+    if t < t0 || t > t1 {
+      return None;
+    }
+    let pos = ray.at(t);
+    let outward_normal = self.shading_normal(u, v);
+    let mut hit_info = HitInfo::new(t, pos, outward_normal, &self.material, ray);
+    hit_info.set_front_face(ray, &outward_normal);
+    Some(hit_info.with_uv(u, v))
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    let bbox = Aabb::new(self.vertex(0), self.vertex(1)).expand(&self.vertex(2));
+    let pad = Vec3::from_one(AABB_PADDING);
+    Aabb::new(*bbox.min() - pad, *bbox.max() + pad)
+  }
+
+  fn name(&self) -> &str {
+    self.mesh.name()
+  }
+}
+
+#[cfg(test)]
+mod triangle_test {
+  use super::*;
+
+  fn unit_triangle(normals: Option<[Vec3; 3]>) -> Triangle {
+    Triangle::new(
+      [
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+      ],
+      normals,
+      "triangle".to_string(),
+      Material::Black,
+    )
+  }
+
+  #[test]
+  fn test_hit() {
+    let triangle = unit_triangle(None);
+    let r1 = Ray::new(Vec3::new(0.25, 0.25, 2.0), Vec3::new(0.0, 0.0, -1.0));
+    let r2 = Ray::new(Vec3::new(0.75, 0.75, 2.0), Vec3::new(0.0, 0.0, -1.0));
+    let r3 = Ray::new(Vec3::new(0.25, 0.25, 2.0), Vec3::new(1.0, 0.0, 0.0));
+
+    let hit = triangle.hit(&r1, 0.001, f64::MAX).unwrap();
+    assert_eq!(hit.get_t(), 2.0);
+    assert_eq!(*hit.get_poisition(), Vec3::new(0.25, 0.25, 0.0));
+    assert_eq!(*hit.get_normal(), Vec3::new(0.0, 0.0, 1.0));
+    assert!(hit.front_face());
+    assert!(triangle.hit(&r2, 0.001, f64::MAX).is_none());
+    assert!(triangle.hit(&r3, 0.001, f64::MAX).is_none());
+  }
+
+  #[test]
+  fn test_tiny_triangle() {
+    // 辺の長さ 1e-5 の三角形に、短い向きベクトルの光線や斜めの光線でも当たる
+    let size = 1.0e-5;
+    let triangle = Triangle::new(
+      [
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(size, 0.0, 0.0),
+        Vec3::new(0.0, size, 0.0),
+      ],
+      None,
+      "tiny".to_string(),
+      Material::Black,
+    );
+    let target = Vec3::new(0.25 * size, 0.25 * size, 0.0);
+    for direction in [
+      Vec3::new(0.0, 0.0, -1.0e-3),
+      Vec3::new(1.0, 0.0, -1.0),
+      Vec3::new(1.0, 1.0, -0.01),
+    ] {
+      let ray = Ray::new(target - direction * 2.0, direction);
+      let (t, u, v) = triangle.intersect(&ray).unwrap();
+      assert!((t - 2.0).abs() < 1e-9, "{:?}", direction);
+      assert!((u - 0.25).abs() < 1e-6 && (v - 0.25).abs() < 1e-6);
+    }
+    // 面と平行な光線と、面積 0 の三角形には当たらない
+    let parallel = Ray::new(Vec3::new(-size, 0.25 * size, 0.0), Vec3::new(1.0, 0.0, 0.0));
+    assert!(triangle.intersect(&parallel).is_none());
+    let degenerate = Triangle::new(
+      [
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(size, 0.0, 0.0),
+        Vec3::new(2.0 * size, 0.0, 0.0),
+      ],
+      None,
+      "degenerate".to_string(),
+      Material::Black,
+    );
+    let ray = Ray::new(Vec3::new(0.5 * size, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(degenerate.intersect(&ray).is_none());
+  }
+
+  #[test]
+  fn test_barycentric() {
+    let triangle = unit_triangle(None);
+    let ray = Ray::new(Vec3::new(0.2, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+    let (t, u, v) = triangle.intersect(&ray).unwrap();
+    assert_eq!(t, 1.0);
+    assert!((u - 0.2).abs() < 1e-12);
+    assert!((v - 0.5).abs() < 1e-12);
+
+    // 裏側から当たった場合は法線が光線側を向く
+    let hit = triangle.hit(&ray, 0.001, f64::MAX).unwrap();
+    assert_eq!(*hit.get_normal(), Vec3::new(0.0, 0.0, -1.0));
+    assert!(!hit.front_face());
+  }
+
+  #[test]
+  fn test_vertex_normals() {
+    let n0 = Vec3::new(0.0, 0.0, 1.0);
+    let n1 = Vec3::new(1.0, 0.0, 1.0).normalize();
+    let triangle = unit_triangle(Some([n0, n1, n0]));
+    let at_v0 = Ray::new(Vec3::new(0.0001, 0.0001, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    let at_v1 = Ray::new(Vec3::new(0.9998, 0.0001, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    let n_at_v0 = *triangle.hit(&at_v0, 0.001, f64::MAX).unwrap().get_normal();
+    let n_at_v1 = *triangle.hit(&at_v1, 0.001, f64::MAX).unwrap().get_normal();
+    assert!((n_at_v0 - n0).norm() < 1e-3);
+    assert!((n_at_v1 - n1).norm() < 1e-3);
+  }
+}
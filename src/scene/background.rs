@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use crate::base::vec::Vec3;
+use crate::object::ray::Ray;
+use crate::scene::environment_map::EnvironmentMap;
+
+// どの物体にも当たらなかった光線が拾う背景の色
+#[derive(Debug, Clone)]
+pub enum Background {
+  // 方向によらず一定の色
+  Solid { color: Vec3 },
+  // up 方向を向くほど top に、逆を向くほど bottom に近づくグラデーション
+  Gradient { bottom: Vec3, top: Vec3, up: Vec3 },
+  // 光を一切出さない背景(光源だけで照らすシーン用)
+  Black,
+  // 全方位画像を光線の方向で引いた色(イメージベースドライティング)
+  EnvironmentMap { map: Arc<EnvironmentMap> },
+}
+
+impl Background {
+  // これまで使っていた白から水色への空のグラデーション
+  pub fn sky() -> Self {
+    Background::Gradient {
+      bottom: Vec3::new(1.0, 1.0, 1.0),
+      top: Vec3::new(0.5, 0.7, 1.0),
+      up: Vec3::new(0.0, 0.0, 1.0),
+    }
+  }
+
+  pub fn color(&self, ray: &Ray) -> Vec3 {
+    match self {
+      Background::Solid { color } => *color,
+      Background::Gradient { bottom, top, up } => {
+        let t = 0.5 * (ray.direction().normalize().dot(&up.normalize()) + 1.0);
+        Vec3::lerp(t.clamp(0.0, 1.0), bottom, top)
+      }
+      Background::Black => Vec3::zero_vector(),
+      Background::EnvironmentMap { map } => map.color(ray.direction()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod background_test {
+  use super::*;
+
+  #[test]
+  fn test_gradient() {
+    let background = Background::Gradient {
+      bottom: Vec3::new(1.0, 0.0, 0.0),
+      top: Vec3::new(0.0, 0.0, 1.0),
+      up: Vec3::new(0.0, 2.0, 0.0),
+    };
+    let up = Ray::new(Vec3::zero_vector(), Vec3::new(0.0, 3.0, 0.0));
+    let down = Ray::new(Vec3::zero_vector(), Vec3::new(0.0, -1.0, 0.0));
+    let side = Ray::new(Vec3::zero_vector(), Vec3::new(1.0, 0.0, 0.0));
+    assert_eq!(background.color(&up), Vec3::new(0.0, 0.0, 1.0));
+    assert_eq!(background.color(&down), Vec3::new(1.0, 0.0, 0.0));
+    assert_eq!(background.color(&side), Vec3::new(0.5, 0.0, 0.5));
+  }
+
+  #[test]
+  fn test_solid_and_black() {
+    let ray = Ray::new(Vec3::zero_vector(), Vec3::new(0.3, -0.2, 0.9));
+    let solid = Background::Solid {
+      color: Vec3::new(0.2, 0.3, 0.4),
+    };
+    assert_eq!(solid.color(&ray), Vec3::new(0.2, 0.3, 0.4));
+    assert_eq!(Background::Black.color(&ray), Vec3::zero_vector());
+  }
+}
@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use image::{ImageResult, Rgb32FImage};
+
+use crate::base::vec::Vec3;
+
+// 正距円筒図法(equirectangular)で展開された全方位画像による環境光
+// シーンはZ軸が上なので、画像の上端が +Z、横方向がZ軸まわりの方位角に対応する
+pub struct EnvironmentMap {
+  image: Rgb32FImage,
+  // Z軸まわりの回転(ラジアン)
+  rotation: f64,
+  // 画像の値に掛ける倍率
+  intensity: f64,
+}
+
+impl EnvironmentMap {
+  // rotation は度数法で指定する
+  pub fn new(image: Rgb32FImage, rotation: f64, intensity: f64) -> Self {
+    Self {
+      image,
+      rotation: rotation.to_radians(),
+      intensity,
+    }
+  }
+
+  // Radiance HDR (.hdr) や OpenEXR (.exr) など image クレートが読める画像を読み込む
+  pub fn load(path: &Path, rotation: f64, intensity: f64) -> ImageResult<Self> {
+    let image = image::open(path)?.into_rgb32f();
+    Ok(Self::new(image, rotation, intensity))
+  }
+
+  // 方向 dir から見える環境の放射輝度
+  pub fn color(&self, dir: &Vec3) -> Vec3 {
+    let d = dir.normalize();
+    let phi = d.get_y().atan2(d.get_x()) + self.rotation;
+    let theta = d.get_z().clamp(-1.0, 1.0).acos();
+    let u = phi / (2.0 * std::f64::consts::PI);
+    let v = theta / std::f64::consts::PI;
+    self.sample_bilinear(u - u.floor(), v) * self.intensity
+  }
+
+  // u は横方向に周回させ、v は端で打ち切って双線形補間する
+  fn sample_bilinear(&self, u: f64, v: f64) -> Vec3 {
+    let (width, height) = self.image.dimensions();
+    let x = u * width as f64 - 0.5;
+    let y = (v * height as f64 - 0.5).clamp(0.0, (height - 1) as f64);
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let fx = x - x0;
+    let fy = y - y0;
+    let x0 = (x0 as i64).rem_euclid(width as i64) as u32;
+    let x1 = (x0 + 1) % width;
+    let y0 = y0 as u32;
+    let y1 = (y0 + 1).min(height - 1);
+
+    let top = Vec3::lerp(fx, &self.texel(x0, y0), &self.texel(x1, y0));
+    let bottom = Vec3::lerp(fx, &self.texel(x0, y1), &self.texel(x1, y1));
+    Vec3::lerp(fy, &top, &bottom)
+  }
+
+  fn texel(&self, x: u32, y: u32) -> Vec3 {
+    let p = self.image.get_pixel(x, y);
+    Vec3::new(p[0] as f64, p[1] as f64, p[2] as f64)
+  }
+}
+
+impl std::fmt::Debug for EnvironmentMap {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("EnvironmentMap")
+      .field("dimensions", &self.image.dimensions())
+      .field("rotation", &self.rotation)
+      .field("intensity", &self.intensity)
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod environment_map_test {
+  use super::*;
+  use image::codecs::hdr::HdrEncoder;
+  use image::Rgb;
+
+  // 上半分が赤、下半分が青で、横方向の位置によって緑成分が変わる画像
+  fn test_image() -> Rgb32FImage {
+    Rgb32FImage::from_fn(8, 4, |x, y| {
+      if y < 2 {
+        Rgb([1.0, x as f32, 0.0])
+      } else {
+        Rgb([0.0, x as f32, 1.0])
+      }
+    })
+  }
+
+  #[test]
+  fn test_color() {
+    let map = EnvironmentMap::new(test_image(), 0.0, 2.0);
+    let up = map.color(&Vec3::new(0.0, 0.0, 1.0));
+    let down = map.color(&Vec3::new(0.0, 0.0, -1.0));
+    assert_eq!(up.get_x(), 2.0);
+    assert_eq!(up.get_z(), 0.0);
+    assert_eq!(down.get_x(), 0.0);
+    assert_eq!(down.get_z(), 2.0);
+  }
+
+  #[test]
+  fn test_rotation() {
+    // 180度回すと +X 方向と -X 方向の見え方が入れ替わる
+    let map = EnvironmentMap::new(test_image(), 0.0, 1.0);
+    let rotated = EnvironmentMap::new(test_image(), 180.0, 1.0);
+    let x = Vec3::new(1.0, 0.0, 0.2);
+    let neg_x = Vec3::new(-1.0, 0.0, 0.2);
+    assert!((map.color(&x) - rotated.color(&neg_x)).norm() < 1e-9);
+    assert!((map.color(&neg_x) - rotated.color(&x)).norm() < 1e-9);
+  }
+
+  #[test]
+  fn test_load_hdr() {
+    let path = std::env::temp_dir().join("ray_tracers_environment_map_test.hdr");
+    let image = test_image();
+    let pixels: Vec<Rgb<f32>> = image.pixels().copied().collect();
+    let file = std::fs::File::create(&path).unwrap();
+    HdrEncoder::new(file).encode(&pixels, 8, 4).unwrap();
+
+    let map = EnvironmentMap::load(&path, 0.0, 1.0).unwrap();
+    let up = map.color(&Vec3::new(0.0, 0.0, 1.0));
+    assert!((up.get_x() - 1.0).abs() < 1e-2);
+    assert!(up.get_z().abs() < 1e-2);
+    std::fs::remove_file(&path).unwrap();
+  }
+}
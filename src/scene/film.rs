@@ -0,0 +1,273 @@
+use crate::base::vec::Vec3;
+use crate::scene::tone_map::ToneMapping;
+use crate::scene::BufferWrapper;
+
+// 相対誤差を求めるときに、これより暗い画素はこの明るさとみなす
+// 真っ暗に近い画素がわずかなノイズでいつまでも収束しないのを防ぐ
+const MIN_LUMINANCE: f64 = 0.01;
+
+// フィルタの重みの和がこれ以下の画素は、重み付き平均が不安定なので使わない
+const MIN_WEIGHT: f64 = 1.0e-6;
+
+// 1画素に集めたサンプルの統計
+#[derive(Debug, Clone, Copy)]
+pub struct PixelStats {
+  sum: Vec3,
+  luminance_sum: f64,
+  luminance_sq_sum: f64,
+  samples: u32,
+}
+
+impl Default for PixelStats {
+  fn default() -> Self {
+    Self {
+      sum: Vec3::zero_vector(),
+      luminance_sum: 0.0,
+      luminance_sq_sum: 0.0,
+      samples: 0,
+    }
+  }
+}
+
+impl PixelStats {
+  pub fn add_sample(&mut self, color: Vec3) {
+    let luminance = Self::luminance(&color);
+    self.sum = self.sum + color;
+    self.luminance_sum += luminance;
+    self.luminance_sq_sum += luminance * luminance;
+    self.samples += 1;
+  }
+
+  pub fn merge(&mut self, other: &PixelStats) {
+    self.sum = self.sum + other.sum;
+    self.luminance_sum += other.luminance_sum;
+    self.luminance_sq_sum += other.luminance_sq_sum;
+    self.samples += other.samples;
+  }
+
+  pub fn samples(&self) -> u32 {
+    self.samples
+  }
+
+  pub fn mean(&self) -> Vec3 {
+    if self.samples == 0 {
+      return Vec3::zero_vector();
+    }
+    self.sum / self.samples as f64
+  }
+
+  // 平均の明るさの標準誤差を、明るさとの比で表したもの
+  // 分散を見積もれない(サンプルが2つ未満の)場合は無限大
+  pub fn relative_error(&self) -> f64 {
+    if self.samples < 2 {
+      return f64::INFINITY;
+    }
+    let n = self.samples as f64;
+    let mean = self.luminance_sum / n;
+    let variance = ((self.luminance_sq_sum - n * mean * mean) / (n - 1.0)).max(0.0);
+    (variance / n).sqrt() / mean.max(MIN_LUMINANCE)
+  }
+
+  fn luminance(color: &Vec3) -> f64 {
+    0.2126 * color.get_x() + 0.7152 * color.get_y() + 0.0722 * color.get_z()
+  }
+}
+
+// 再構成フィルタで重み付けしたサンプルの和と、重みの和
+#[derive(Debug, Clone, Copy)]
+struct Splat {
+  sum: Vec3,
+  weight: f64,
+}
+
+impl Default for Splat {
+  fn default() -> Self {
+    Self {
+      sum: Vec3::zero_vector(),
+      weight: 0.0,
+    }
+  }
+}
+
+// 1つのタイルを描画する間に、タイルの周り(フィルタの半径分)まで含めて足し込んだサンプル
+// 描画が終わってから Film にまとめて足す
+#[derive(Debug, Clone)]
+pub struct SplatBuffer {
+  x0: u32,
+  y0: u32,
+  width: u32,
+  height: u32,
+  splats: Vec<Splat>,
+}
+
+impl SplatBuffer {
+  // [x0, x1) x [y0, y1) の範囲の画素に足し込む
+  pub fn new(x0: u32, y0: u32, x1: u32, y1: u32) -> Self {
+    let (width, height) = (x1 - x0, y1 - y0);
+    Self {
+      x0,
+      y0,
+      width,
+      height,
+      splats: vec![Splat::default(); (width * height) as usize],
+    }
+  }
+
+  // 画素 (i, j) に重み weight で color を足す。範囲外の画素は無視する
+  pub fn add(&mut self, i: u32, j: u32, color: Vec3, weight: f64) {
+    if i < self.x0 || j < self.y0 || i >= self.x0 + self.width || j >= self.y0 + self.height {
+      return;
+    }
+    let splat = &mut self.splats[((i - self.x0) + (j - self.y0) * self.width) as usize];
+    splat.sum = splat.sum + color * weight;
+    splat.weight += weight;
+  }
+}
+
+// 描画結果を浮動小数点のまま貯めておく画像
+// 何回かに分けて描画したサンプルを画素ごとに足し合わせていく
+#[derive(Debug, Clone)]
+pub struct Film {
+  width: u32,
+  height: u32,
+  pixels: Vec<PixelStats>,
+  splats: Vec<Splat>,
+}
+
+impl Film {
+  pub fn new(width: u32, height: u32) -> Self {
+    Self {
+      width,
+      height,
+      pixels: vec![PixelStats::default(); (width * height) as usize],
+      splats: vec![Splat::default(); (width * height) as usize],
+    }
+  }
+
+  pub fn width(&self) -> u32 {
+    self.width
+  }
+
+  pub fn height(&self) -> u32 {
+    self.height
+  }
+
+  pub fn pixel(&self, i: u32, j: u32) -> &PixelStats {
+    &self.pixels[(i + j * self.width) as usize]
+  }
+
+  pub fn pixel_mut(&mut self, i: u32, j: u32) -> &mut PixelStats {
+    &mut self.pixels[(i + j * self.width) as usize]
+  }
+
+  pub fn merge_splats(&mut self, buffer: &SplatBuffer) {
+    for y in 0..buffer.height {
+      for x in 0..buffer.width {
+        let src = &buffer.splats[(x + y * buffer.width) as usize];
+        let dst = &mut self.splats[(buffer.x0 + x + (buffer.y0 + y) * self.width) as usize];
+        dst.sum = dst.sum + src.sum;
+        dst.weight += src.weight;
+      }
+    }
+  }
+
+  // フィルタで重み付けした平均。重みが足りない(負の裾ばかり当たった)画素はその画素のサンプルの平均にする
+  pub fn color(&self, i: u32, j: u32) -> Vec3 {
+    let index = (i + j * self.width) as usize;
+    let splat = &self.splats[index];
+    if splat.weight <= MIN_WEIGHT {
+      return self.pixels[index].mean();
+    }
+    splat.sum / splat.weight
+  }
+
+  // 各画素に使ったサンプル数(左上から行ごと)
+  pub fn samples(&self) -> Vec<u32> {
+    self.pixels.iter().map(|p| p.samples()).collect()
+  }
+
+  // 明るさを切り詰めずに、線形の RGB を左上から行ごとに並べたもの(HDR 画像の書き出し用)
+  pub fn to_linear(&self) -> Vec<f32> {
+    (0..self.height)
+      .flat_map(|j| (0..self.width).map(move |i| (i, j)))
+      .flat_map(|(i, j)| {
+        let c = self.color(i, j);
+        [c.get_x() as f32, c.get_y() as f32, c.get_z() as f32]
+      })
+      .collect()
+  }
+
+  // tone_mapping で表示用の色にした 8bit の画像
+  pub fn to_buffer(&self, tone_mapping: &ToneMapping) -> BufferWrapper {
+    BufferWrapper(
+      (0..self.height)
+        .flat_map(|j| (0..self.width).map(move |i| (i, j)))
+        .map(|(i, j)| tone_mapping.to_color(self.color(i, j)).to_u32())
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod film_test {
+  use super::*;
+
+  #[test]
+  fn test_pixel_stats() {
+    let mut stats = PixelStats::default();
+    assert_eq!(stats.mean(), Vec3::zero_vector());
+    assert!(stats.relative_error().is_infinite());
+    stats.add_sample(Vec3::from_one(1.0));
+    stats.add_sample(Vec3::from_one(3.0));
+    assert_eq!(stats.samples(), 2);
+    assert_eq!(stats.mean(), Vec3::from_one(2.0));
+    // 不偏分散 2、標準誤差 1、平均 2
+    assert!((stats.relative_error() - 0.5).abs() < 1e-12);
+
+    let mut merged = PixelStats::default();
+    merged.add_sample(Vec3::from_one(1.0));
+    let mut other = PixelStats::default();
+    other.add_sample(Vec3::from_one(3.0));
+    merged.merge(&other);
+    assert_eq!(merged.mean(), stats.mean());
+    assert_eq!(merged.relative_error(), stats.relative_error());
+
+    // 同じ値ばかりなら誤差は 0
+    let mut flat = PixelStats::default();
+    for _ in 0..4 {
+      flat.add_sample(Vec3::new(0.2, 0.4, 0.6));
+    }
+    assert!(flat.relative_error() < 1e-6);
+  }
+
+  #[test]
+  fn test_splat() {
+    let mut film = Film::new(3, 2);
+    // 範囲の外に足したものは捨てる
+    let mut left = SplatBuffer::new(0, 0, 2, 2);
+    left.add(1, 0, Vec3::from_one(1.0), 1.0);
+    left.add(2, 0, Vec3::from_one(5.0), 1.0);
+    let mut right = SplatBuffer::new(1, 0, 3, 1);
+    right.add(1, 0, Vec3::from_one(4.0), 0.5);
+    film.merge_splats(&left);
+    film.merge_splats(&right);
+    assert_eq!(film.color(1, 0), Vec3::from_one(2.0));
+    assert_eq!(film.color(2, 0), Vec3::zero_vector());
+    // 重みがない画素は、その画素のサンプルの平均
+    film.pixel_mut(0, 1).add_sample(Vec3::from_one(0.5));
+    assert_eq!(film.color(0, 1), Vec3::from_one(0.5));
+  }
+
+  #[test]
+  fn test_film() {
+    let mut film = Film::new(3, 2);
+    film.pixel_mut(2, 1).add_sample(Vec3::from_one(1.0));
+    assert_eq!(film.color(2, 1), Vec3::from_one(1.0));
+    assert_eq!(film.samples(), vec![0, 0, 0, 0, 0, 1]);
+    assert_eq!(film.to_linear()[12..], [0.0, 0.0, 0.0, 1.0, 1.0, 1.0]);
+    let buffer = film.to_buffer(&ToneMapping::default());
+    assert_eq!(buffer.0.len(), 6);
+    assert_eq!(buffer.0[5], 0xffffffff);
+    assert_eq!(buffer.0[0], 0xff000000);
+  }
+}
@@ -0,0 +1,137 @@
+use std::f64::consts::PI;
+
+// サンプルから画素の色を組み立てる再構成フィルタ
+// 各サンプルを、画素の中心からの距離に応じた重みで半径 radius (画素単位)以内の画素に足し込む
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+  // 自分の画素だけを単純に平均する(radius 0.5 の場合)
+  Box { radius: f64 },
+  // 中心から線形に減る三角形
+  Tent { radius: f64 },
+  // 標準偏差 radius / 3 のガウス関数を、radius で 0 になるように下げたもの
+  Gaussian { radius: f64 },
+  // Mitchell–Netravali (B = C = 1/3)。負の値を持ち、輪郭がくっきりする
+  Mitchell { radius: f64 },
+  // radius を窓の大きさとする Lanczos の窓付き sinc 関数
+  Lanczos { radius: f64 },
+}
+
+impl Filter {
+  pub fn radius(&self) -> f64 {
+    match self {
+      Filter::Box { radius }
+      | Filter::Tent { radius }
+      | Filter::Gaussian { radius }
+      | Filter::Mitchell { radius }
+      | Filter::Lanczos { radius } => *radius,
+    }
+  }
+
+  // 画素の中心から (x, y) だけ離れたサンプルの重み。縦横それぞれの重みの積にする
+  pub fn evaluate(&self, x: f64, y: f64) -> f64 {
+    self.evaluate_1d(x) * self.evaluate_1d(y)
+  }
+
+  fn evaluate_1d(&self, x: f64) -> f64 {
+    let r = self.radius();
+    match self {
+      // 隣り合う画素の境目に落ちたサンプルが両方に入らないように、片側だけ含める
+      Filter::Box { .. } => {
+        if -r <= x && x < r {
+          1.0
+        } else {
+          0.0
+        }
+      }
+      Filter::Tent { .. } => (1.0 - x.abs() / r).max(0.0),
+      Filter::Gaussian { .. } => {
+        let sigma = r / 3.0;
+        let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+        (gaussian(x) - gaussian(r)).max(0.0)
+      }
+      Filter::Mitchell { .. } => {
+        // 元の定義域 [-2, 2] を [-r, r] に広げる
+        let x = (2.0 * x / r).abs();
+        let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+        let value = if x < 1.0 {
+          (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b)
+        } else if x < 2.0 {
+          (-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+        } else {
+          0.0
+        };
+        value / 6.0
+      }
+      Filter::Lanczos { .. } => {
+        if x.abs() >= r {
+          return 0.0;
+        }
+        Self::sinc(x) * Self::sinc(x / r)
+      }
+    }
+  }
+
+  fn sinc(x: f64) -> f64 {
+    if x.abs() < 1.0e-8 {
+      return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+  }
+}
+
+impl Default for Filter {
+  fn default() -> Self {
+    Filter::Box { radius: 0.5 }
+  }
+}
+
+#[cfg(test)]
+mod filter_test {
+  use super::*;
+
+  fn filters() -> Vec<Filter> {
+    vec![
+      Filter::Box { radius: 0.5 },
+      Filter::Tent { radius: 1.0 },
+      Filter::Gaussian { radius: 1.5 },
+      Filter::Mitchell { radius: 2.0 },
+      Filter::Lanczos { radius: 2.0 },
+    ]
+  }
+
+  #[test]
+  fn test_filter_support() {
+    for filter in filters() {
+      let r = filter.radius();
+      // 中心で最大になり、半径の外では 0 になる
+      assert!(filter.evaluate(0.0, 0.0) > 0.0);
+      assert!(filter.evaluate(0.0, 0.0) >= filter.evaluate(0.3, 0.2));
+      assert_eq!(filter.evaluate(r + 0.01, 0.0), 0.0);
+      assert_eq!(filter.evaluate(0.0, -r - 0.01), 0.0);
+      // 左右対称
+      assert!((filter.evaluate_1d(0.37) - filter.evaluate_1d(-0.37)).abs() < 1e-12);
+    }
+  }
+
+  #[test]
+  fn test_filter_values() {
+    let box_filter = Filter::Box { radius: 0.5 };
+    assert_eq!(box_filter.evaluate_1d(-0.5), 1.0);
+    assert_eq!(box_filter.evaluate_1d(0.5), 0.0);
+    assert_eq!(Filter::Tent { radius: 2.0 }.evaluate_1d(1.0), 0.5);
+    // Mitchell は中心で (6 - 2B) / 6、裾で負になる
+    let mitchell = Filter::Mitchell { radius: 2.0 };
+    assert!((mitchell.evaluate_1d(0.0) - 8.0 / 9.0).abs() < 1e-12);
+    assert!(mitchell.evaluate_1d(1.5) < 0.0);
+    // Lanczos は整数の位置で 0
+    let lanczos = Filter::Lanczos { radius: 3.0 };
+    assert_eq!(lanczos.evaluate_1d(0.0), 1.0);
+    assert!(lanczos.evaluate_1d(1.0).abs() < 1e-12);
+    assert!(lanczos.evaluate_1d(1.5) < 0.0);
+  }
+}
@@ -0,0 +1,32 @@
+// 1画素の色を求める方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Integrator {
+  // 散乱方向を材質に選ばせて光線を追跡するだけの素朴な方法
+  Path,
+  // 衝突のたびに光源へ影の光線を飛ばして直接光を求め(next event estimation)、
+  // 散乱方向のサンプリングとは多重重点的サンプリング(MIS)で組み合わせる
+  Mis,
+}
+
+// 2つのサンプリング方法のうち、確率密度 pdf で選んだサンプルに掛ける重み(パワーヒューリスティック)
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+  let a = pdf * pdf;
+  let b = other_pdf * other_pdf;
+  if a + b <= 0.0 {
+    return 0.0;
+  }
+  a / (a + b)
+}
+
+#[cfg(test)]
+mod integrator_test {
+  use super::*;
+
+  #[test]
+  fn test_power_heuristic() {
+    assert_eq!(power_heuristic(1.0, 1.0), 0.5);
+    assert_eq!(power_heuristic(1.0, 0.0), 1.0);
+    assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+    assert!((power_heuristic(3.0, 1.0) + power_heuristic(1.0, 3.0) - 1.0).abs() < 1e-12);
+  }
+}
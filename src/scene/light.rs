@@ -0,0 +1,138 @@
+use crate::base::vec::Vec3;
+
+// 形状を持たない光源。カメラからの光線には当たらず、影の光線で直接光だけを与える
+#[derive(Debug, Clone)]
+pub enum Light {
+  // 全方向に等しく光を放つ点。明るさは距離の2乗に反比例して弱まる
+  Point {
+    position: Vec3,
+    intensity: Vec3,
+  },
+  // direction を中心とする円錐の中だけを照らす点光源
+  // 中心から inner_angle までは intensity のまま、outer_angle に向かって滑らかに暗くなる(角度はラジアン)
+  Spot {
+    position: Vec3,
+    direction: Vec3,
+    intensity: Vec3,
+    inner_angle: f64,
+    outer_angle: f64,
+  },
+  // 無限遠から direction の向きに平行に届く光(太陽光)。irradiance は光に垂直な面が受ける明るさ
+  Directional {
+    direction: Vec3,
+    irradiance: Vec3,
+  },
+}
+
+impl Light {
+  // point に届く光を (光源へ向かう単位ベクトル, 光源までの距離, 光に垂直な面が受ける明るさ) で返す
+  // 光が届かない位置では None
+  pub fn illuminate(&self, point: &Vec3) -> Option<(Vec3, f64, Vec3)> {
+    match self {
+      Light::Point {
+        position,
+        intensity,
+      } => {
+        let (to_light, distance) = Self::to_position(position, point)?;
+        Some((to_light, distance, *intensity / (distance * distance)))
+      }
+      Light::Spot {
+        position,
+        direction,
+        intensity,
+        inner_angle,
+        outer_angle,
+      } => {
+        let (to_light, distance) = Self::to_position(position, point)?;
+        let cos = -to_light.dot(&direction.normalize());
+        let falloff = Self::smoothstep(outer_angle.cos(), inner_angle.cos(), cos);
+        if falloff <= 0.0 {
+          return None;
+        }
+        Some((
+          to_light,
+          distance,
+          *intensity * (falloff / (distance * distance)),
+        ))
+      }
+      Light::Directional {
+        direction,
+        irradiance,
+      } => Some((direction.normalize() * -1.0, f64::INFINITY, *irradiance)),
+    }
+  }
+
+  fn to_position(position: &Vec3, point: &Vec3) -> Option<(Vec3, f64)> {
+    let d = *position - *point;
+    let distance = d.norm();
+    if distance <= 0.0 {
+      return None;
+    }
+    Some((d / distance, distance))
+  }
+
+  // x が edge0 以下で 0、edge1 以上で 1 になり、その間を滑らかにつなぐ
+  fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    if edge1 <= edge0 {
+      return if x >= edge1 { 1.0 } else { 0.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+  }
+}
+
+#[cfg(test)]
+mod light_test {
+  use super::*;
+
+  #[test]
+  fn test_point_light() {
+    let light = Light::Point {
+      position: Vec3::new(0.0, 0.0, 2.0),
+      intensity: Vec3::from_one(8.0),
+    };
+    let (to_light, distance, value) = light.illuminate(&Vec3::zero_vector()).unwrap();
+    assert_eq!(to_light, Vec3::new(0.0, 0.0, 1.0));
+    assert_eq!(distance, 2.0);
+    assert_eq!(value, Vec3::from_one(2.0));
+    // 2倍離れると 1/4 になる
+    let (_, _, far) = light.illuminate(&Vec3::new(0.0, 0.0, -2.0)).unwrap();
+    assert_eq!(far, Vec3::from_one(0.5));
+  }
+
+  #[test]
+  fn test_spot_light() {
+    let light = Light::Spot {
+      position: Vec3::new(0.0, 0.0, 1.0),
+      direction: Vec3::new(0.0, 0.0, -2.0),
+      intensity: Vec3::from_one(1.0),
+      inner_angle: 20f64.to_radians(),
+      outer_angle: 40f64.to_radians(),
+    };
+    let at_angle = |deg: f64| {
+      let rad = deg.to_radians();
+      light
+        .illuminate(&Vec3::new(rad.tan(), 0.0, 0.0))
+        .map(|(_, distance, value)| value.get_x() * distance * distance)
+    };
+    assert!((at_angle(0.0).unwrap() - 1.0).abs() < 1e-9);
+    assert!((at_angle(15.0).unwrap() - 1.0).abs() < 1e-9);
+    let edge = at_angle(30.0).unwrap();
+    assert!(edge > 0.0 && edge < 1.0);
+    assert!(at_angle(45.0).is_none());
+    // 光源の後ろは照らさない
+    assert!(light.illuminate(&Vec3::new(0.0, 0.0, 2.0)).is_none());
+  }
+
+  #[test]
+  fn test_directional_light() {
+    let light = Light::Directional {
+      direction: Vec3::new(0.0, 0.0, -3.0),
+      irradiance: Vec3::new(1.0, 0.9, 0.8),
+    };
+    let (to_light, distance, value) = light.illuminate(&Vec3::new(5.0, -3.0, 1.0)).unwrap();
+    assert_eq!(to_light, Vec3::new(0.0, 0.0, 1.0));
+    assert!(distance.is_infinite());
+    assert_eq!(value, Vec3::new(1.0, 0.9, 0.8));
+  }
+}
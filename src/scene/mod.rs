@@ -1,13 +1,41 @@
-use crate::base::color::{Color, WHITE};
+pub mod background;
+pub mod environment_map;
+pub mod film;
+pub mod filter;
+pub mod integrator;
+pub mod light;
+pub mod output;
+pub mod sampler;
+pub mod tone_map;
+
+use crate::base::color::Color;
+use crate::base::math::{get_uniform_random, mix_seed, new_rand_gen, seed_from_time, RandGen};
 use crate::base::vec::Vec3;
 use crate::config::config::Config;
 use crate::config::scene_config::CameraConfig;
+use crate::object::bvh::Bvh;
 use crate::object::camera::Camera;
+use crate::object::hit::HitInfo;
 use crate::object::ray::Ray;
 use crate::object::shape::Shape;
+use crate::object::shape_list::ShapeList;
+use crate::scene::background::Background;
+use crate::scene::film::{Film, PixelStats, SplatBuffer};
+use crate::scene::filter::Filter;
+use crate::scene::integrator::{power_heuristic, Integrator};
+use crate::scene::light::Light;
+use crate::scene::sampler::{Sampler, SamplerKind};
+use crate::scene::tone_map::ToneMapping;
 
 use std::borrow::Borrow;
-use std::cell::RefCell;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+// 描画を分割するタイルの一辺のピクセル数
+const TILE_SIZE: u32 = 32;
+
+// ロシアンルーレットを始める散乱回数の既定値
+pub const DEFAULT_ROULETTE_DEPTH: u32 = 5;
 
 pub struct BufferWrapper(Vec<u32>);
 
@@ -19,12 +47,33 @@ impl Borrow<[u8]> for BufferWrapper {
 
 pub struct Scene {
   camera: Camera,
-  background_color: Color,
+  background: Background,
   width: u32,
   height: u32,
   super_samples: u32,
   max_scatter_depth: u32,
-  objects: RefCell<Vec<Box<dyn Shape>>>,
+  // この回数以上散乱した経路をロシアンルーレットで打ち切る
+  roulette_depth: u32,
+  // 1画素あたりのサンプル数の上限。super_samples は1回の描画で足すサンプル数になる
+  max_samples: u32,
+  // 画素の明るさの相対誤差がこれ以下になったらサンプリングをやめる。None なら max_samples まで続ける
+  noise_threshold: Option<f64>,
+  time_limit: Option<Duration>,
+  // 描画スレッド数。0 の場合は利用可能なCPU数
+  threads: usize,
+  // 乱数のシード。各ピクセルの乱数生成器はこれとピクセル位置から作る
+  seed: u64,
+  objects: ShapeList,
+  // objects から構築したBVH。None の場合は線形探索する
+  bvh: Option<Bvh>,
+  integrator: Integrator,
+  sampler: SamplerKind,
+  filter: Filter,
+  tone_mapping: ToneMapping,
+  // objects のうち光源として直接サンプリングする物体の添字
+  light_shapes: Vec<usize>,
+  // 形状を持たない光源
+  lights: Vec<Light>,
 }
 
 impl Scene {
@@ -37,12 +86,25 @@ impl Scene {
   ) -> Self {
     Scene {
       camera: camera,
-      background_color: WHITE,
+      background: Background::sky(),
       width: width,
       height: height,
       super_samples: super_samples,
       max_scatter_depth: max_scatter_depth,
-      objects: RefCell::new(vec![]),
+      roulette_depth: DEFAULT_ROULETTE_DEPTH,
+      max_samples: super_samples,
+      noise_threshold: None,
+      time_limit: None,
+      threads: 0,
+      seed: seed_from_time(),
+      objects: ShapeList::new(vec![], "objects".to_string()),
+      bvh: Some(Bvh::build::<Box<dyn Shape>>(&[])),
+      integrator: Integrator::Path,
+      sampler: SamplerKind::Random,
+      filter: Filter::default(),
+      tone_mapping: ToneMapping::default(),
+      light_shapes: vec![],
+      lights: vec![],
     }
   }
 
@@ -59,15 +121,75 @@ impl Scene {
       .objects
       .iter()
       .map(|o| o.to_object())
-      .collect();
+      .collect::<Vec<_>>();
+    let bvh = if render_config.use_bvh {
+      Some(Bvh::build(&objects))
+    } else {
+      None
+    };
+    let light_shapes = Self::find_lights(&objects);
+    // 1画素もサンプリングしない設定では真っ黒な画像になるだけなので止める
+    let max_samples = render_config.max_samples.unwrap_or(render_config.sampling);
+    if max_samples == 0 {
+      match render_config.max_samples {
+        Some(_) => panic!("render.max_samples must be at least 1"),
+        None => panic!("render.sampling must be at least 1 when render.max_samples is not set"),
+      }
+    }
+    if let Some(threshold) = render_config.noise_threshold {
+      if !threshold.is_finite() || threshold < 0.0 {
+        panic!(
+          "render.noise_threshold must be a non-negative finite number, got {}",
+          threshold
+        );
+      }
+    }
+    // max_samples がなければ sampling で止まるので、1回の描画で終わってノイズの判定や時間制限は効かない
+    if render_config.max_samples.is_none() {
+      if render_config.noise_threshold.is_some() {
+        eprintln!("warning: render.noise_threshold has no effect without render.max_samples");
+      }
+      if render_config.time_limit.is_some() {
+        eprintln!("warning: render.time_limit has no effect without render.max_samples");
+      }
+    }
     Scene {
       camera: camera,
-      background_color: WHITE,
+      background: config
+        .scene_config()
+        .background
+        .as_ref()
+        .map_or(Background::sky(), |b| b.to_background()),
       width: render_config.width,
       height: render_config.height,
       super_samples: render_config.sampling,
       max_scatter_depth: render_config.max_scatter_depth,
-      objects: RefCell::new(objects),
+      roulette_depth: render_config.roulette_depth,
+      max_samples,
+      noise_threshold: render_config.noise_threshold,
+      time_limit: render_config.time_limit.map(|limit| {
+        Duration::try_from_secs_f64(limit).unwrap_or_else(|e| {
+          panic!(
+            "render.time_limit must be a non-negative number of seconds, got {}: {}",
+            limit, e
+          )
+        })
+      }),
+      threads: render_config.threads,
+      seed: render_config.seed.unwrap_or_else(seed_from_time),
+      objects: ShapeList::new(objects, "objects".to_string()),
+      bvh,
+      integrator: render_config.integrator.to_integrator(),
+      sampler: render_config.sampler.to_sampler_kind(),
+      filter: render_config.filter.to_filter(),
+      tone_mapping: render_config.tone_mapping(),
+      light_shapes,
+      lights: config
+        .scene_config()
+        .lights
+        .iter()
+        .map(|l| l.to_light())
+        .collect(),
     }
   }
 
@@ -76,48 +198,990 @@ impl Scene {
   }
 
   pub fn set_background_color(&mut self, color: Color) {
-    self.background_color = color;
+    self.background = Background::Solid {
+      color: color.to_vec3(),
+    };
+  }
+
+  pub fn set_background(&mut self, background: Background) {
+    self.background = background;
   }
 
   pub fn add_object(&mut self, object: Box<dyn Shape>) {
-    self.objects.borrow_mut().push(object);
+    self.objects.push(object);
+    if self.bvh.is_some() {
+      self.bvh = Some(Bvh::build(self.objects.shapes()));
+    }
+    self.light_shapes = Self::find_lights(self.objects.shapes());
+  }
+
+  fn find_lights(objects: &[Box<dyn Shape>]) -> Vec<usize> {
+    objects
+      .iter()
+      .enumerate()
+      .filter(|(_, o)| o.is_light())
+      .map(|(i, _)| i)
+      .collect()
+  }
+
+  // BVHを使うかどうかを切り替える
+  pub fn set_use_bvh(&mut self, use_bvh: bool) {
+    self.bvh = if use_bvh {
+      Some(Bvh::build(self.objects.shapes()))
+    } else {
+      None
+    };
+  }
+
+  pub fn set_threads(&mut self, threads: usize) {
+    self.threads = threads;
+  }
+
+  pub fn set_seed(&mut self, seed: u64) {
+    self.seed = seed;
+  }
+
+  pub fn add_light(&mut self, light: Light) {
+    self.lights.push(light);
+  }
+
+  pub fn set_max_samples(&mut self, max_samples: u32) {
+    self.max_samples = max_samples;
+  }
+
+  pub fn set_noise_threshold(&mut self, noise_threshold: Option<f64>) {
+    self.noise_threshold = noise_threshold;
+  }
+
+  pub fn set_time_limit(&mut self, time_limit: Option<Duration>) {
+    self.time_limit = time_limit;
+  }
+
+  pub fn set_roulette_depth(&mut self, roulette_depth: u32) {
+    self.roulette_depth = roulette_depth;
+  }
+
+  pub fn set_sampler(&mut self, sampler: SamplerKind) {
+    self.sampler = sampler;
+  }
+
+  pub fn set_filter(&mut self, filter: Filter) {
+    self.filter = filter;
+  }
+
+  pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+    self.tone_mapping = tone_mapping;
+  }
+
+  pub fn tone_mapping(&self) -> &ToneMapping {
+    &self.tone_mapping
+  }
+
+  pub fn set_integrator(&mut self, integrator: Integrator) {
+    self.integrator = integrator;
   }
 
   pub fn render(&self) -> BufferWrapper {
-    let mut buf = vec![0u32; (self.width * self.height) as usize];
-
-    for i in 0..self.width {
-      for j in 0..self.height {
-        let mut col = Vec3::new(0.0, 0.0, 0.0);
-        for _ in 0..self.super_samples {
-          let u = (i as f64) / (self.width as f64);
-          let v = (j as f64) / (self.height as f64);
-          let ray = self.camera.get_ray(u, v);
-          let c = self.gen_color(&ray, &self.objects.borrow(), 0);
-          col = col + c;
+    self.render_film().to_buffer(&self.tone_mapping)
+  }
+
+  // 全画素に super_samples ずつサンプルを足す描画を繰り返す
+  // ノイズが noise_threshold 以下になった画素と max_samples に達した画素はそれ以上サンプリングしない
+  // すべての画素が止まるか、time_limit を過ぎたら(その回の描画が終わってから)終える
+  // 1回の描画でサンプルが1つも増えなかった場合も、いつまでも終わらないのを防ぐために終える
+  pub fn render_film(&self) -> Film {
+    let start = Instant::now();
+    let mut film = Film::new(self.width, self.height);
+    let mut active = vec![true; (self.width * self.height) as usize];
+    let mut total_samples = 0;
+    for pass in 0.. {
+      self.render_pass(&mut film, &active, pass);
+      active = self.active_pixels(&film);
+      let timed_out = self
+        .time_limit
+        .is_some_and(|limit| start.elapsed() >= limit);
+      let samples: u64 = film.samples().iter().map(|n| *n as u64).sum();
+      let stalled = samples == total_samples;
+      total_samples = samples;
+      if !active.contains(&true) || timed_out || stalled {
+        break;
+      }
+    }
+    film
+  }
+
+  // まだサンプルを足す必要がある画素
+  // まれにしか当たらない明るい経路がある画素は、少ないサンプルでは分散を小さく見積もって暗いまま止まりやすい
+  // そのため周囲 3x3 画素の相対誤差の最大値で判定し、ノイズの多い領域の中の画素は止めない
+  fn active_pixels(&self, film: &Film) -> Vec<bool> {
+    let mut active = Vec::with_capacity((self.width * self.height) as usize);
+    for j in 0..self.height {
+      for i in 0..self.width {
+        if film.pixel(i, j).samples() >= self.max_samples {
+          active.push(false);
+          continue;
+        }
+        let Some(threshold) = self.noise_threshold else {
+          active.push(true);
+          continue;
+        };
+        let error = (j.saturating_sub(1)..(j + 2).min(self.height))
+          .flat_map(|y| (i.saturating_sub(1)..(i + 2).min(self.width)).map(move |x| (x, y)))
+          .map(|(x, y)| film.pixel(x, y).relative_error())
+          .fold(0.0, f64::max);
+        active.push(error > threshold);
+      }
+    }
+    active
+  }
+
+  // 画像をタイルに分割し、各スレッドが空いているタイルを順に取って描画する
+  fn render_pass(&self, film: &mut Film, active: &[bool], pass: u32) {
+    let tiles_x = self.width.div_ceil(TILE_SIZE);
+    let tiles_y = self.height.div_ceil(TILE_SIZE);
+    let tile_count = tiles_x * tiles_y;
+    let next_tile = AtomicU32::new(0);
+
+    let current: &Film = film;
+    let mut rendered: Vec<(u32, (Vec<PixelStats>, SplatBuffer))> = std::thread::scope(|s| {
+      let handles: Vec<_> = (0..self.thread_count(tile_count as usize))
+        .map(|_| {
+          s.spawn(|| {
+            let mut tiles = vec![];
+            loop {
+              let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+              if tile >= tile_count {
+                break;
+              }
+              tiles.push((
+                tile,
+                self.render_tile(self.tile_rect(tile, tiles_x), current, active, pass),
+              ));
+            }
+            tiles
+          })
+        })
+        .collect();
+      handles
+        .into_iter()
+        .flat_map(|h| h.join().expect("render thread panicked"))
+        .collect()
+    });
+
+    // 隣のタイルと重なる画素もあるので、スレッド数によらず同じ順番で足す
+    rendered.sort_by_key(|(tile, _)| *tile);
+    for (tile, (pixels, splats)) in rendered {
+      film.merge_splats(&splats);
+      let (x0, y0, x1, _) = self.tile_rect(tile, tiles_x);
+      let tile_width = x1 - x0;
+      for (n, stats) in pixels.iter().enumerate() {
+        let n = n as u32;
+        film
+          .pixel_mut(x0 + n % tile_width, y0 + n / tile_width)
+          .merge(stats);
+      }
+    }
+  }
+
+  fn thread_count(&self, tile_count: usize) -> usize {
+    let threads = if self.threads == 0 {
+      std::thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+      self.threads
+    };
+    threads.clamp(1, tile_count.max(1))
+  }
+
+  // タイル番号から描画範囲 (x0, y0, x1, y1) を求める。x1, y1 は含まない
+  fn tile_rect(&self, tile: u32, tiles_x: u32) -> (u32, u32, u32, u32) {
+    let x0 = (tile % tiles_x) * TILE_SIZE;
+    let y0 = (tile / tiles_x) * TILE_SIZE;
+    (
+      x0,
+      y0,
+      (x0 + TILE_SIZE).min(self.width),
+      (y0 + TILE_SIZE).min(self.height),
+    )
+  }
+
+  fn render_tile(
+    &self,
+    (x0, y0, x1, y1): (u32, u32, u32, u32),
+    film: &Film,
+    active: &[bool],
+    pass: u32,
+  ) -> (Vec<PixelStats>, SplatBuffer) {
+    let mut pixels = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+    // サンプルはフィルタの半径内にある隣のタイルの画素にも足し込む
+    let pad = self.filter.radius().ceil() as u32;
+    let mut splats = SplatBuffer::new(
+      x0.saturating_sub(pad),
+      y0.saturating_sub(pad),
+      (x1 + pad).min(self.width),
+      (y1 + pad).min(self.height),
+    );
+    for j in y0..y1 {
+      for i in x0..x1 {
+        if active[(i + j * self.width) as usize] {
+          pixels.push(self.render_pixel(i, j, film.pixel(i, j), pass, &mut splats));
+        } else {
+          pixels.push(PixelStats::default());
+        }
+      }
+    }
+    (pixels, splats)
+  }
+
+  // 1画素分のサンプルを描画して、その統計を返す
+  // 各サンプルはフィルタで重み付けして splats にも足す
+  fn render_pixel(
+    &self,
+    i: u32,
+    j: u32,
+    current: &PixelStats,
+    pass: u32,
+    splats: &mut SplatBuffer,
+  ) -> PixelStats {
+    let mut stats = PixelStats::default();
+    // 1回目はピクセル位置だけから、2回目以降は描画の回数も混ぜて乱数生成器を作る
+    let pixel_index = (i + j * self.width) as u64;
+    let pixel_count = (self.width * self.height) as u64;
+    let mut rng = new_rand_gen(mix_seed(self.seed, pixel_index + pass as u64 * pixel_count));
+    // super_samples が 0 でも1回に少なくとも1サンプル足し、描画が進まなくならないようにする
+    let samples = self
+      .super_samples
+      .max(1)
+      .min(self.max_samples - current.samples());
+    // 画素内の位置とレンズ上の位置は、光線の散乱に使う乱数とは別のシードで決める
+    let mut sampler = Sampler::new(
+      self.sampler,
+      mix_seed(!self.seed, pixel_index),
+      current.samples(),
+      samples,
+    );
+    for _ in 0..samples {
+      let sample = sampler.next_sample(&mut rng);
+      let u = (i as f64 + sample.pixel.0) / (self.width as f64);
+      let v = (j as f64 + sample.pixel.1) / (self.height as f64);
+      let ray = self.camera.get_ray(u, v, sample.lens);
+      let c = match self.integrator {
+        Integrator::Path => self.gen_color(&ray, &self.objects, &mut rng),
+        Integrator::Mis => self.gen_color_mis(&ray, &self.objects, &mut rng),
+      };
+      stats.add_sample(c);
+      self.splat(
+        splats,
+        i as f64 + sample.pixel.0,
+        j as f64 + sample.pixel.1,
+        c,
+      );
+    }
+    stats
+  }
+
+  // 画像上の位置 (x, y) のサンプルを、中心がフィルタの半径内にある画素に足す
+  fn splat(&self, splats: &mut SplatBuffer, x: f64, y: f64, color: Vec3) {
+    let radius = self.filter.radius();
+    let range = |p: f64, size: u32| {
+      let first = (p - 0.5 - radius).ceil().max(0.0) as u32;
+      let last = ((p - 0.5 + radius).floor() as i64).min(size as i64 - 1);
+      first as i64..=last
+    };
+    for py in range(y, self.height) {
+      for px in range(x, self.width) {
+        let weight = self
+          .filter
+          .evaluate(x - (px as f64 + 0.5), y - (py as f64 + 0.5));
+        if weight != 0.0 {
+          splats.add(px as u32, py as u32, color, weight);
+        }
+      }
+    }
+  }
+
+  // 光線と最初に交差する(=最も手前の)物体を探す
+  fn hit_objects<'a>(
+    &self,
+    ray: &Ray,
+    shapes: &'a ShapeList,
+  ) -> Option<(&'a dyn Shape, HitInfo<'a>)> {
+    match &self.bvh {
+      Some(bvh) => bvh
+        .hit(shapes.shapes(), ray, 0.001, f64::MAX)
+        .map(|(shape, hit_info)| (shape.as_ref(), hit_info)),
+      None => shapes.closest_hit(ray, 0.001, f64::MAX),
+    }
+  }
+
+  // 光線の始点から距離 distance までの間に物体があるか
+  fn is_occluded(&self, ray: &Ray, shapes: &ShapeList, distance: f64) -> bool {
+    let t_max = distance.min(f64::MAX) - 0.001;
+    match &self.bvh {
+      Some(bvh) => bvh.hit(shapes.shapes(), ray, 0.001, t_max).is_some(),
+      None => shapes.closest_hit(ray, 0.001, t_max).is_some(),
+    }
+  }
+
+  // 形状を持たない光源から衝突点に直接届く光。光源ごとに影の光線を1本ずつ飛ばす
+  fn direct_light(&self, ray: &Ray, hit_info: &HitInfo, shapes: &ShapeList) -> Vec3 {
+    let material = hit_info.get_hit_material();
+    let position = hit_info.get_poisition();
+    let mut color = Vec3::zero_vector();
+    for light in self.lights.iter() {
+      let Some((to_light, distance, value)) = light.illuminate(position) else {
+        continue;
+      };
+      let Some((f_cos, _)) = material.bsdf(ray, hit_info, &to_light) else {
+        continue;
+      };
+      if f_cos.near_zero() || self.is_occluded(&Ray::new(*position, to_light), shapes, distance) {
+        continue;
+      }
+      color = color + f_cos * value;
+    }
+    color
+  }
+
+  // 光線を散乱させながら追跡し、経路上で拾った光を足し合わせる
+  // throughput はカメラからここまでの減衰率の積
+  fn gen_color(&self, ray: &Ray, shapes: &ShapeList, rng: &mut RandGen) -> Vec3 {
+    let mut color = Vec3::zero_vector();
+    let mut throughput = Vec3::from_one(1.0);
+    let mut ray = Ray::new(*ray.origin(), *ray.direction());
+    for depth in 0.. {
+      let Some((_, hit_info)) = self.hit_objects(&ray, shapes) else {
+        color = color + throughput * self.background.color(&ray);
+        break;
+      };
+      let material = hit_info.get_hit_material();
+      // 光源に当たった場合はその光を足し合わせる
+      color = color
+        + throughput * (material.emitted(&hit_info) + self.direct_light(&ray, &hit_info, shapes));
+      let Some((scattered, attenuation)) = material.scatter(&ray, &hit_info, rng) else {
+        break;
+      };
+      if depth >= self.max_scatter_depth {
+        break;
+      }
+      throughput = throughput * attenuation;
+      if !self.survive_roulette(depth + 1, &mut throughput, rng) {
+        break;
+      }
+      ray = scattered;
+    }
+    color
+  }
+
+  // gen_color に光源の直接サンプリングを加えたもの
+  fn gen_color_mis(&self, ray: &Ray, shapes: &ShapeList, rng: &mut RandGen) -> Vec3 {
+    let mut color = Vec3::zero_vector();
+    let mut throughput = Vec3::from_one(1.0);
+    let mut ray = Ray::new(*ray.origin(), *ray.direction());
+    // 前の衝突で材質が ray の方向を選んだ確率密度。前の衝突で光源をサンプリングしていない場合は None
+    let mut bsdf_pdf: Option<f64> = None;
+    for depth in 0.. {
+      let Some((shape, hit_info)) = self.hit_objects(&ray, shapes) else {
+        color = color + throughput * self.background.color(&ray);
+        break;
+      };
+      let material = hit_info.get_hit_material();
+      let mut emitted = material.emitted(&hit_info);
+      // 光源の直接サンプリングでも同じ光を数えているので、2つの方法の重みで分け合う
+      if let Some(pdf) = bsdf_pdf {
+        if shape.is_light() {
+          emitted =
+            emitted * power_heuristic(pdf, self.light_pdf(shapes, ray.origin(), ray.direction()));
         }
-        col = col / (self.super_samples as f64);
-        buf[(i + j * self.width) as usize] = Color::from_vec3_gamma(col, 255, 2.2).to_u32();
       }
+      color = color + throughput * (emitted + self.direct_light(&ray, &hit_info, shapes));
+
+      let Some((scattered, attenuation)) = material.scatter(&ray, &hit_info, rng) else {
+        break;
+      };
+      if depth >= self.max_scatter_depth {
+        break;
+      }
+      bsdf_pdf = if self.light_shapes.is_empty() {
+        None
+      } else {
+        material
+          .bsdf(&ray, &hit_info, scattered.direction())
+          .map(|(_, pdf)| pdf)
+      };
+      if bsdf_pdf.is_some() {
+        color = color + throughput * self.sample_light(&ray, &hit_info, shapes, rng);
+      }
+      throughput = throughput * attenuation;
+      if !self.survive_roulette(depth + 1, &mut throughput, rng) {
+        break;
+      }
+      ray = scattered;
+    }
+    color
+  }
+
+  // ロシアンルーレット: roulette_depth 回以上散乱した経路は、減衰率の最大成分の確率で生き残らせる
+  // 生き残った経路は確率で割って明るくするので、打ち切っても期待値は変わらない
+  fn survive_roulette(&self, depth: u32, throughput: &mut Vec3, rng: &mut RandGen) -> bool {
+    if depth < self.roulette_depth {
+      return true;
+    }
+    let p = throughput
+      .get_x()
+      .max(throughput.get_y())
+      .max(throughput.get_z())
+      .min(1.0);
+    if p <= 0.0 || get_uniform_random(rng) >= p {
+      return false;
     }
-    BufferWrapper(buf)
+    *throughput = *throughput / p;
+    true
+  }
+
+  // 光源を1つ選んでその方向へ影の光線を飛ばし、届いた直接光を返す
+  fn sample_light(
+    &self,
+    ray: &Ray,
+    hit_info: &HitInfo,
+    shapes: &ShapeList,
+    rng: &mut RandGen,
+  ) -> Vec3 {
+    let index = ((get_uniform_random(rng) * self.light_shapes.len() as f64) as usize)
+      .min(self.light_shapes.len() - 1);
+    let light = &shapes.shapes()[self.light_shapes[index]];
+    let origin = hit_info.get_poisition();
+    let Some(direction) = light.sample_direction(origin, rng) else {
+      return Vec3::zero_vector();
+    };
+    let light_pdf = self.light_pdf(shapes, origin, &direction);
+    let Some((f_cos, bsdf_pdf)) = hit_info.get_hit_material().bsdf(ray, hit_info, &direction)
+    else {
+      return Vec3::zero_vector();
+    };
+    if light_pdf <= 0.0 || f_cos.near_zero() {
+      return Vec3::zero_vector();
+    }
+    // 途中で別の物体に遮られていれば影になる
+    let shadow_ray = Ray::new(*origin, direction);
+    match self.hit_objects(&shadow_ray, shapes) {
+      Some((shape, light_hit)) if shape.is_light() => {
+        let emitted = light_hit.get_hit_material().emitted(&light_hit);
+        f_cos * emitted * (power_heuristic(light_pdf, bsdf_pdf) / light_pdf)
+      }
+      _ => Vec3::zero_vector(),
+    }
+  }
+
+  // 光源をサンプリングして direction を選ぶ確率密度。光源は等確率で選ぶ
+  fn light_pdf(&self, shapes: &ShapeList, origin: &Vec3, direction: &Vec3) -> f64 {
+    let sum: f64 = self
+      .light_shapes
+      .iter()
+      .map(|i| shapes.shapes()[*i].direction_pdf(origin, direction))
+      .sum();
+    sum / self.light_shapes.len() as f64
+  }
+}
+
+#[cfg(test)]
+mod scene_test {
+  use super::*;
+  use crate::base::matrix::Mat4;
+  use crate::base::onb::Onb;
+  use crate::object::cuboid::Cuboid;
+  use crate::object::hit::HitInfo;
+  use crate::object::material::{CustomMaterial, Material};
+  use crate::object::sphere::Sphere;
+  use crate::object::transform::Transformed;
+  use crate::object::triangle::Triangle;
+  use std::sync::Arc;
+
+  fn sky_scene(threads: usize) -> Scene {
+    // 口径0にしてレンズ由来の乱数を無くし、シードも固定して空の色だけの決定的な画像にする
+    let camera = Camera::new(
+      Vec3::new(0.0, -10.0, 0.0),
+      Vec3::new(0.0, 0.0, 0.0),
+      Vec3::new(0.0, 0.0, 1.0),
+      40.0,
+      70.0 / 45.0,
+      0.0,
+      10.0,
+    );
+    let mut scene = Scene::new(camera, 70, 45, 1, 5);
+    scene.set_threads(threads);
+    scene.set_seed(0);
+    scene
+  }
+
+  fn sphere_scene(threads: usize, seed: u64) -> Scene {
+    let mut scene = sky_scene(threads);
+    scene.set_seed(seed);
+    scene.add_object(Box::new(Sphere::new(
+      Vec3::new(0.0, 0.0, 0.0),
+      1.0,
+      "diffuse".to_string(),
+      Material::Lambertian {
+        albedo: Vec3::new(0.5, 0.5, 0.5).into(),
+      },
+    )));
+    scene.add_object(Box::new(Sphere::new(
+      Vec3::new(1.5, 0.0, 0.0),
+      0.5,
+      "metal".to_string(),
+      Material::Metal {
+        albedo: Vec3::new(0.8, 0.6, 0.2).into(),
+        fuzzy: 0.3,
+      },
+    )));
+    scene
+  }
+
+  #[test]
+  fn test_render_with_seed() {
+    // 同じシードなら、スレッド数が違ってもビット単位で同じ画像になる
+    let image1 = sphere_scene(1, 7).render();
+    let image2 = sphere_scene(3, 7).render();
+    let image3 = sphere_scene(1, 8).render();
+    assert_eq!(image1.0, image2.0);
+    assert_ne!(image1.0, image3.0);
+  }
+
+  #[test]
+  fn test_render_tiles() {
+    // タイルの大きさで割り切れない解像度でも、スレッド数によらず同じ画像になる
+    let single = sky_scene(1).render();
+    let multi = sky_scene(4).render();
+    assert_eq!(single.0.len(), 70 * 45);
+    assert!(single.0.iter().all(|p| *p != 0));
+    assert_eq!(single.0, multi.0);
+  }
+
+  #[test]
+  fn test_adaptive_sampling() {
+    let adaptive = |threads: usize| {
+      let mut scene = sphere_scene(threads, 3);
+      scene.set_max_samples(64);
+      scene.set_noise_threshold(Some(0.02));
+      scene.render_film()
+    };
+    let film = adaptive(1);
+    // 空しか見えない画素は毎回同じ色になるので、分散を見積もれる2サンプルで止まる
+    assert_eq!(film.pixel(0, 0).samples(), 2);
+    // 拡散反射する球の画素はノイズが多いので、より多くのサンプルを使う
+    assert!(film.pixel(35, 22).samples() > 2);
+    assert!(film.samples().iter().all(|n| (2..=64).contains(n)));
+    // スレッド数によらず同じ結果になる
+    assert_eq!(
+      film.to_buffer(&ToneMapping::default()).0,
+      adaptive(4).to_buffer(&ToneMapping::default()).0
+    );
+    assert_eq!(film.samples(), adaptive(4).samples());
+
+    // 時間切れなら最初の描画だけで終える
+    let mut scene = sphere_scene(1, 3);
+    scene.set_max_samples(64);
+    scene.set_time_limit(Some(Duration::ZERO));
+    assert!(scene.render_film().samples().iter().all(|n| *n == 1));
+
+    // 1回に足すサンプル数が 0 でも、1つずつ足して max_samples で終える
+    let mut scene = sphere_scene(1, 3);
+    scene.super_samples = 0;
+    scene.set_max_samples(4);
+    assert!(scene.render_film().samples().iter().all(|n| *n == 4));
+    // サンプルを足せる画素がなければ、1回の描画で終える
+    let mut scene = sphere_scene(1, 3);
+    scene.set_max_samples(0);
+    scene.set_noise_threshold(Some(0.02));
+    assert!(scene.render_film().samples().iter().all(|n| *n == 0));
+  }
+
+  fn config_with_render(render: &str) -> Config {
+    let yaml = format!(
+      "output_name: out.png
+render:
+  width: 4
+  height: 3
+  max_scatter_depth: 5
+{}
+scene:
+  objects: []",
+      render
+    );
+    serde_yaml::from_str(&yaml).unwrap()
+  }
+
+  #[test]
+  fn test_sampling_config() {
+    let scene = Scene::build_from_config(&config_with_render("  sampling: 0\n  max_samples: 8"));
+    assert_eq!(scene.max_samples, 8);
+    let scene = Scene::build_from_config(&config_with_render(
+      "  sampling: 4\n  max_samples: 8\n  noise_threshold: 0.0",
+    ));
+    assert_eq!(scene.noise_threshold, Some(0.0));
+  }
+
+  #[test]
+  #[should_panic(
+    expected = "render.sampling must be at least 1 when render.max_samples is not set"
+  )]
+  fn test_zero_sampling() {
+    Scene::build_from_config(&config_with_render("  sampling: 0"));
+  }
+
+  #[test]
+  #[should_panic(expected = "render.max_samples must be at least 1")]
+  fn test_zero_max_samples() {
+    Scene::build_from_config(&config_with_render("  sampling: 4\n  max_samples: 0"));
+  }
+
+  #[test]
+  #[should_panic(
+    expected = "render.noise_threshold must be a non-negative finite number, got -0.1"
+  )]
+  fn test_negative_noise_threshold() {
+    Scene::build_from_config(&config_with_render(
+      "  sampling: 4\n  max_samples: 8\n  noise_threshold: -0.1",
+    ));
+  }
+
+  #[test]
+  #[should_panic(expected = "render.noise_threshold must be a non-negative finite number, got NaN")]
+  fn test_nan_noise_threshold() {
+    Scene::build_from_config(&config_with_render(
+      "  sampling: 4\n  max_samples: 8\n  noise_threshold: .nan",
+    ));
   }
 
-  fn gen_color(&self, ray: &Ray, shapes: &Vec<Box<dyn Shape>>, depth: u32) -> Vec3 {
-    for shape in shapes.iter() {
-      if let Some(hit_info) = shape.hit(ray, 0.001, std::f64::MAX) {
-        if let Some((scattered, attenuation)) = shape.scatter(&ray, &hit_info) {
-          if depth < self.max_scatter_depth {
-            let c = self.gen_color(&scattered, shapes, depth + 1);
-            return c * attenuation;
-          }
+  #[test]
+  fn test_reconstruction_filter() {
+    let render = |filter: Filter, threads: usize| {
+      let mut scene = sphere_scene(threads, 5);
+      scene.set_max_samples(8);
+      scene.super_samples = 8;
+      scene.set_filter(filter);
+      scene.render_film()
+    };
+    // 隣の画素同士の明るさの差の合計
+    let roughness = |film: &Film| {
+      let mut total = 0.0;
+      for j in 0..film.height() {
+        for i in 1..film.width() {
+          total += (film.color(i, j) - film.color(i - 1, j)).norm();
         }
-        //return (hit_info.get_normal().clone() + Vec3::new(1.0, 1.0, 1.0)).dir(0.5);
-        return Vec3::zero_vector();
       }
+      total
+    };
+    // 既定の box フィルタは、その画素のサンプルの平均と同じ
+    let sharp = render(Filter::default(), 1);
+    assert!((sharp.color(35, 22) - sharp.pixel(35, 22).mean()).norm() < 1e-9);
+    // 半径の大きいフィルタはタイルの境目をまたいでも、スレッド数によらず同じ画像になる
+    for filter in [
+      Filter::Tent { radius: 1.0 },
+      Filter::Gaussian { radius: 3.0 },
+      Filter::Mitchell { radius: 2.0 },
+      Filter::Lanczos { radius: 3.0 },
+    ] {
+      let film = render(filter, 1);
+      assert_eq!(
+        film.to_buffer(&ToneMapping::default()).0,
+        render(filter, 4).to_buffer(&ToneMapping::default()).0
+      );
+    }
+    // ガウスフィルタはノイズと輪郭をぼかす
+    let blurred = render(Filter::Gaussian { radius: 3.0 }, 1);
+    assert!(roughness(&blurred) < roughness(&sharp) * 0.8);
+  }
+
+  #[test]
+  fn test_sampler_antialiasing() {
+    // 白い背景の前に黒い球を置くと、輪郭の画素は画素内の位置をずらしたサンプルで中間の色になる
+    for sampler in [
+      SamplerKind::Random,
+      SamplerKind::Stratified,
+      SamplerKind::Halton,
+    ] {
+      let mut scene = sky_scene(2);
+      scene.set_background(Background::Solid {
+        color: Vec3::from_one(1.0),
+      });
+      scene.add_object(Box::new(Sphere::new(
+        Vec3::zero_vector(),
+        1.0,
+        "black".to_string(),
+        Material::Black,
+      )));
+      scene.set_sampler(sampler);
+      scene.super_samples = 16;
+      scene.set_max_samples(16);
+      let film = scene.render_film();
+      let gray = (0..scene.height)
+        .flat_map(|j| (0..scene.width).map(move |i| (i, j)))
+        .filter(|(i, j)| {
+          let c = film.color(*i, *j).get_x();
+          c > 0.05 && c < 0.95
+        })
+        .count();
+      assert!(gray > 20, "{:?}: {}", sampler, gray);
+      assert_eq!(film.to_buffer(scene.tone_mapping()).0, scene.render().0);
+    }
+  }
+
+  // 光線をそのまま通して赤以外を吸収し、自身は緑に光る材質
+  #[derive(Debug)]
+  struct RedFilter;
+
+  impl CustomMaterial for RedFilter {
+    fn scatter(
+      &self,
+      incoming_ray: &Ray,
+      hit_info: &HitInfo,
+      _rng: &mut RandGen,
+    ) -> Option<(Ray, Vec3)> {
+      let ray = Ray::new(*hit_info.get_poisition(), *incoming_ray.direction());
+      Some((ray, Vec3::new(1.0, 0.0, 0.0)))
+    }
+
+    fn emitted(&self, _hit_info: &HitInfo) -> Vec3 {
+      Vec3::new(0.0, 0.25, 0.0)
+    }
+  }
+
+  #[test]
+  fn test_custom_material() {
+    // カメラを囲む球に独自の材質を使う
+    let mut scene = sky_scene(1);
+    scene.add_object(Box::new(Sphere::new(
+      Vec3::new(0.0, 0.0, 0.0),
+      100.0,
+      "filter".to_string(),
+      Material::Custom(Arc::new(RedFilter)),
+    )));
+    let image = scene.render();
+    for p in image.0.iter() {
+      let (r, g, b) = (p & 0xff, (p >> 8) & 0xff, (p >> 16) & 0xff);
+      assert!(r > 0 && g > 0);
+      assert_eq!(b, 0);
+    }
+  }
+
+  #[test]
+  fn test_mis_matches_path() {
+    // 黒い空の下で、小さな光源に照らされた床を見る
+    let mut scene = sky_scene(1);
+    scene.set_background(Background::Solid {
+      color: Vec3::zero_vector(),
+    });
+    scene.add_object(Box::new(Sphere::new(
+      Vec3::new(0.0, 0.0, -100.0),
+      99.0,
+      "floor".to_string(),
+      Material::Lambertian {
+        albedo: Vec3::new(0.5, 0.5, 0.5).into(),
+      },
+    )));
+    scene.add_object(Box::new(Sphere::new(
+      Vec3::new(1.0, 0.0, 1.0),
+      0.5,
+      "light".to_string(),
+      Material::DiffuseLight {
+        emit: Vec3::from_one(4.0),
+      },
+    )));
+    assert_eq!(scene.light_shapes, vec![1]);
+    assert_mis_matches_path(&scene);
+  }
+
+  // 床を見下ろす同じ光線について、2つの方法で求めた色の平均と分散を比べる
+  // MIS は同じ平均で、分散が小さくなる
+  fn assert_mis_matches_path(scene: &Scene) {
+    let ray = Ray::new(Vec3::new(0.0, -5.0, 1.0), Vec3::new(0.0, 5.0, -2.0));
+    let n = 100000;
+    let mut rng = new_rand_gen(0);
+    let mut stats = |integrator: Integrator| {
+      let samples: Vec<f64> = (0..n)
+        .map(|_| {
+          let c = match integrator {
+            Integrator::Path => scene.gen_color(&ray, &scene.objects, &mut rng),
+            Integrator::Mis => scene.gen_color_mis(&ray, &scene.objects, &mut rng),
+          };
+          c.get_x()
+        })
+        .collect();
+      let mean = samples.iter().sum::<f64>() / n as f64;
+      let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n as f64;
+      (mean, variance)
+    };
+    let (path_mean, path_variance) = stats(Integrator::Path);
+    let (mis_mean, mis_variance) = stats(Integrator::Mis);
+    assert!(path_mean > 0.0);
+    assert!(
+      (path_mean - mis_mean).abs() < 0.05 * path_mean,
+      "{} {}",
+      path_mean,
+      mis_mean
+    );
+    assert!(mis_variance < 0.5 * path_variance);
+  }
+
+  #[test]
+  fn test_transformed_light() {
+    // 座標変換を掛けた光る球も光源として直接サンプリングする
+    let mut scene = sky_scene(1);
+    scene.set_background(Background::Black);
+    scene.add_object(Box::new(Sphere::new(
+      Vec3::new(0.0, 0.0, -100.0),
+      99.0,
+      "floor".to_string(),
+      Material::Lambertian {
+        albedo: Vec3::new(0.5, 0.5, 0.5).into(),
+      },
+    )));
+    let light = Sphere::new(
+      Vec3::zero_vector(),
+      1.0,
+      "light".to_string(),
+      Material::DiffuseLight {
+        emit: Vec3::from_one(4.0),
+      },
+    );
+    scene.add_object(Box::new(Transformed::new(
+      Box::new(light),
+      Mat4::translation(&Vec3::new(1.0, 0.0, 1.0)) * Mat4::scale(&Vec3::new(0.8, 0.5, 0.3)),
+    )));
+    assert_eq!(scene.light_shapes, vec![1]);
+    assert_mis_matches_path(&scene);
+  }
+
+  #[test]
+  fn test_mesh_light_not_sampled() {
+    // 三角形やメッシュの光源は直接サンプリングしないが、散乱した光線が当たれば明るくなる
+    let mut scene = sky_scene(1);
+    scene.set_background(Background::Black);
+    scene.add_object(Box::new(Sphere::new(
+      Vec3::new(0.0, 0.0, -100.0),
+      99.0,
+      "floor".to_string(),
+      Material::Lambertian {
+        albedo: Vec3::new(0.5, 0.5, 0.5).into(),
+      },
+    )));
+    scene.add_object(Box::new(Triangle::new(
+      [
+        Vec3::new(0.0, -1.0, 2.0),
+        Vec3::new(2.0, -1.0, 2.0),
+        Vec3::new(0.0, 1.0, 2.0),
+      ],
+      None,
+      "panel".to_string(),
+      Material::DiffuseLight {
+        emit: Vec3::from_one(4.0),
+      },
+    )));
+    assert!(scene.light_shapes.is_empty());
+    let ray = Ray::new(Vec3::new(0.0, -5.0, 1.0), Vec3::new(0.0, 5.0, -2.0));
+    let mut rng = new_rand_gen(0);
+    let sum: f64 = (0..2000)
+      .map(|_| scene.gen_color_mis(&ray, &scene.objects, &mut rng).get_x())
+      .sum();
+    assert!(sum > 0.0);
+  }
+
+  #[test]
+  fn test_analytic_lights() {
+    // 黒い空の下の床を、真上の点光源で照らす
+    let mut scene = sky_scene(1);
+    scene.set_background(Background::Black);
+    scene.add_object(Box::new(Cuboid::new(
+      Vec3::new(-10.0, -10.0, -1.0),
+      Vec3::new(10.0, 10.0, 0.0),
+      "floor".to_string(),
+      Material::Lambertian {
+        albedo: Vec3::new(0.5, 0.5, 0.5).into(),
+      },
+    )));
+    scene.add_light(Light::Point {
+      position: Vec3::new(0.0, 0.0, 2.0),
+      intensity: Vec3::from_one(4.0),
+    });
+    let ray = Ray::new(Vec3::new(0.0, -1.0, 1.0), Vec3::new(0.0, 1.0, -1.0));
+    let mut rng = new_rand_gen(0);
+    // 床に届く明るさは 4 / 2^2 = 1 で、拡散反射の BSDF は albedo / π
+    let expected = 0.5 / std::f64::consts::PI;
+    // どちらの方法でも影の光線で同じ明るさになる
+    let path = scene.gen_color(&ray, &scene.objects, &mut rng);
+    let mis = scene.gen_color_mis(&ray, &scene.objects, &mut rng);
+    assert!((path.get_x() - expected).abs() < 1e-9);
+    assert!((mis.get_x() - expected).abs() < 1e-9);
+
+    // 光源との間に球を置くと影になる
+    scene.add_object(Box::new(Sphere::new(
+      Vec3::new(0.0, 0.0, 1.0),
+      0.2,
+      "occluder".to_string(),
+      Material::Lambertian {
+        albedo: Vec3::new(0.5, 0.5, 0.5).into(),
+      },
+    )));
+    assert_eq!(
+      scene.gen_color(&ray, &scene.objects, &mut rng),
+      Vec3::zero_vector()
+    );
+
+    // 平行光源は距離によらず一定の明るさで照らす
+    scene.lights.clear();
+    scene.add_light(Light::Directional {
+      direction: Vec3::new(1.0, 0.0, -1.0),
+      irradiance: Vec3::from_one(2.0),
+    });
+    let c = scene.gen_color(&ray, &scene.objects, &mut rng);
+    let cos = std::f64::consts::FRAC_1_SQRT_2;
+    assert!((c.get_x() - 2.0 * cos * expected).abs() < 1e-9);
+  }
+
+  // 拡散反射しながら自らも光る壁
+  #[derive(Debug)]
+  struct GlowingWall;
+
+  impl CustomMaterial for GlowingWall {
+    fn scatter(
+      &self,
+      _incoming_ray: &Ray,
+      hit_info: &HitInfo,
+      rng: &mut RandGen,
+    ) -> Option<(Ray, Vec3)> {
+      let dir = Onb::from_w(hit_info.get_normal()).local(&Vec3::gen_random_cosine_direction(rng));
+      Some((
+        Ray::new(*hit_info.get_poisition(), dir),
+        Vec3::from_one(0.8),
+      ))
+    }
+
+    fn emitted(&self, _hit_info: &HitInfo) -> Vec3 {
+      Vec3::from_one(1.0)
+    }
+  }
+
+  #[test]
+  fn test_russian_roulette() {
+    // 反射率 0.8 で明るさ 1 の壁に囲まれた内部では、どこを見ても 1 / (1 - 0.8) = 5 の明るさになる
+    let mut scene = sky_scene(1);
+    scene.max_scatter_depth = 10000;
+    scene.add_object(Box::new(Sphere::new(
+      Vec3::new(0.0, 0.0, 0.0),
+      10.0,
+      "room".to_string(),
+      Material::Custom(Arc::new(GlowingWall)),
+    )));
+    let ray = Ray::new(Vec3::zero_vector(), Vec3::new(0.3, 1.0, 0.2));
+    let mut rng = new_rand_gen(4);
+    let n = 20000;
+    for roulette_depth in [0, 3] {
+      scene.set_roulette_depth(roulette_depth);
+      let mean = (0..n)
+        .map(|_| scene.gen_color(&ray, &scene.objects, &mut rng).get_x())
+        .sum::<f64>()
+        / n as f64;
+      assert!((mean - 5.0).abs() < 0.1, "{}: {}", roulette_depth, mean);
     }
-    let mut t: f64 = 0.5f64 * (ray.direction().normalize().get_z() + 1.0_f64);
-    t = t.clamp(0.0, 1.0);
-    Vec3::lerp(t, &Vec3::new(1.0, 1.0, 1.0), &Vec3::new(0.5, 0.7, 1.0))
   }
 }
@@ -0,0 +1,151 @@
+use std::borrow::Borrow;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use image::codecs::hdr::HdrEncoder;
+use image::{ImageBuffer, ImageFormat, ImageResult, Rgb};
+
+use crate::scene::film::Film;
+use crate::scene::tone_map::ToneMapping;
+
+// 描画結果を書き出す画像の形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+  // 8bit の画像。トーンマッピングして表示用の値にする
+  Png,
+  Jpeg,
+  // 32bit 浮動小数点の線形な RGB をそのまま書き出す(OpenExr)
+  Exr,
+  // Radiance HDR (RGBE)。線形な RGB を共通の指数付きで書き出す
+  Hdr,
+}
+
+impl OutputFormat {
+  // ファイルの拡張子から形式を決める。知らない拡張子なら None
+  pub fn from_path(path: &Path) -> Option<Self> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+      "png" => Some(OutputFormat::Png),
+      "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+      "exr" => Some(OutputFormat::Exr),
+      "hdr" => Some(OutputFormat::Hdr),
+      _ => None,
+    }
+  }
+}
+
+// film を path に書き出す。format が None なら拡張子から決め、それでも決まらなければ 8bit の画像として image に任せる
+// tone_mapping は 8bit の画像にだけ使い、HDR 形式には線形な値をそのまま書く
+pub fn save_film(
+  film: &Film,
+  path: &Path,
+  format: Option<OutputFormat>,
+  tone_mapping: &ToneMapping,
+) -> ImageResult<()> {
+  let (width, height) = (film.width(), film.height());
+  let save_ldr = |format: Option<ImageFormat>| {
+    let buf = film.to_buffer(tone_mapping);
+    match format {
+      Some(format) => image::save_buffer_with_format(
+        path,
+        buf.borrow(),
+        width,
+        height,
+        image::ColorType::Rgba8,
+        format,
+      ),
+      None => image::save_buffer(path, buf.borrow(), width, height, image::ColorType::Rgba8),
+    }
+  };
+  match format.or_else(|| OutputFormat::from_path(path)) {
+    None => save_ldr(None),
+    Some(OutputFormat::Png) => save_ldr(Some(ImageFormat::Png)),
+    Some(OutputFormat::Jpeg) => save_ldr(Some(ImageFormat::Jpeg)),
+    Some(OutputFormat::Exr) => {
+      let buf: ImageBuffer<Rgb<f32>, Vec<f32>> =
+        ImageBuffer::from_raw(width, height, film.to_linear()).expect("film size mismatch");
+      buf.save_with_format(path, ImageFormat::OpenExr)
+    }
+    Some(OutputFormat::Hdr) => {
+      let pixels: Vec<Rgb<f32>> = film
+        .to_linear()
+        .chunks(3)
+        .map(|c| Rgb([c[0], c[1], c[2]]))
+        .collect();
+      let writer = BufWriter::new(File::create(path)?);
+      HdrEncoder::new(writer).encode(&pixels, width as usize, height as usize)
+    }
+  }
+}
+
+#[cfg(test)]
+mod output_test {
+  use super::*;
+  use crate::base::vec::Vec3;
+
+  #[test]
+  fn test_format_from_path() {
+    let format = |name: &str| OutputFormat::from_path(Path::new(name));
+    assert_eq!(format("out/a.png"), Some(OutputFormat::Png));
+    assert_eq!(format("a.JPG"), Some(OutputFormat::Jpeg));
+    assert_eq!(format("a.exr"), Some(OutputFormat::Exr));
+    assert_eq!(format("a.hdr"), Some(OutputFormat::Hdr));
+    assert_eq!(format("a.bmp"), None);
+    assert_eq!(format("a"), None);
+  }
+
+  #[test]
+  fn test_save_film() {
+    // 1 を超える明るさも HDR 形式なら失われない
+    let mut film = Film::new(2, 1);
+    film.pixel_mut(0, 0).add_sample(Vec3::new(4.0, 0.5, 0.25));
+    film.pixel_mut(1, 0).add_sample(Vec3::new(0.0, 1.0, 16.0));
+    let dir = std::env::temp_dir().join(format!("ray_tracers_output_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let expected = film.to_linear();
+    let check = |name: &str, values: Vec<f32>| {
+      assert_eq!(values.len(), expected.len(), "{}", name);
+      // RGBE は3色で指数を共有するので、明るい色と並ぶ暗い色は少しずれる
+      for (value, expected) in values.iter().zip(expected.iter()) {
+        assert!(
+          (value - expected).abs() <= 0.01 + expected * 0.01,
+          "{}",
+          name
+        );
+      }
+    };
+    for (name, format) in [
+      ("film.exr", None),
+      ("film_exr.bin", Some(OutputFormat::Exr)),
+    ] {
+      let path = dir.join(name);
+      save_film(&film, &path, format, &ToneMapping::default()).unwrap();
+      let image = image::io::Reader::open(&path)
+        .unwrap()
+        .with_guessed_format()
+        .unwrap()
+        .decode()
+        .unwrap()
+        .into_rgb32f();
+      check(name, image.into_raw());
+    }
+    // image::open は .hdr を 8bit にしてしまうので、HDR のまま読む
+    let path = dir.join("film.hdr");
+    save_film(&film, &path, None, &ToneMapping::default()).unwrap();
+    let reader = std::io::BufReader::new(File::open(&path).unwrap());
+    let pixels = image::codecs::hdr::HdrDecoder::new(reader)
+      .unwrap()
+      .read_image_hdr()
+      .unwrap();
+    check("film.hdr", pixels.iter().flat_map(|p| p.0).collect());
+
+    // 8bit の画像は 1 で切り詰める
+    let path = dir.join("film.png");
+    save_film(&film, &path, None, &ToneMapping::default()).unwrap();
+    let image = image::open(&path).unwrap().into_rgba8();
+    assert_eq!(image.get_pixel(0, 0).0[0], 255);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,203 @@
+use rand::seq::SliceRandom;
+
+use crate::base::math::{get_uniform_random, mix_seed, new_rand_gen, RandGen};
+
+// カメラの光線を作るときに使う、画素内の位置とレンズ上の位置の選び方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplerKind {
+  // それぞれ独立な一様乱数
+  Random,
+  // 画素とレンズを格子に区切り、各マスから1つずつ選ぶ(ジッター法)
+  Stratified,
+  // Halton 列(低食い違い量列)。画素ごとにずらして使う
+  Halton,
+}
+
+// 1つのサンプルでカメラに渡す値。どちらも 0 から 1 の2次元の点
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraSample {
+  // 画素内の位置
+  pub pixel: (f64, f64),
+  // レンズ上の位置
+  pub lens: (f64, f64),
+}
+
+// 1画素に続けて足すサンプルの位置を決める
+#[derive(Debug, Clone)]
+pub struct Sampler {
+  kind: SamplerKind,
+  // 画素内で何番目のサンプルか
+  index: u32,
+  // Stratified: 画素とレンズの格子の分割数と、各サンプルに割り当てるマスの順番
+  grid: (u32, u32),
+  pixel_cells: Vec<u32>,
+  lens_cells: Vec<u32>,
+  // Halton: 画素ごとに列をずらす量(Cranley-Patterson 回転)
+  offsets: [f64; 4],
+}
+
+// Halton 列の各次元に使う素数。画素内の位置に 2, 3、レンズ上の位置に 5, 7 を使う
+const HALTON_BASES: [u32; 4] = [2, 3, 5, 7];
+
+impl Sampler {
+  // pixel_seed は画素ごとに決まるシード。first_index はこの画素にすでに足したサンプル数、count はこれから足すサンプル数
+  pub fn new(kind: SamplerKind, pixel_seed: u64, first_index: u32, count: u32) -> Self {
+    let mut sampler = Self {
+      kind,
+      index: first_index,
+      grid: (1, 1),
+      pixel_cells: vec![],
+      lens_cells: vec![],
+      offsets: [0.0; 4],
+    };
+    match kind {
+      SamplerKind::Random => {}
+      SamplerKind::Stratified => {
+        // count 個以上のマスに区切り、マスの順番を混ぜて count 個を使う
+        let nx = (count as f64).sqrt().ceil().max(1.0) as u32;
+        let ny = count.div_ceil(nx).max(1);
+        let mut rng = new_rand_gen(mix_seed(pixel_seed, first_index as u64));
+        let cells = |rng: &mut RandGen| {
+          let mut cells: Vec<u32> = (0..nx * ny).collect();
+          cells.shuffle(rng);
+          cells
+        };
+        sampler.grid = (nx, ny);
+        sampler.pixel_cells = cells(&mut rng);
+        sampler.lens_cells = cells(&mut rng);
+      }
+      SamplerKind::Halton => {
+        // 同じ画素なら何回に分けて描画しても同じだけずらし、列の続きを使う
+        let mut rng = new_rand_gen(pixel_seed);
+        for offset in sampler.offsets.iter_mut() {
+          *offset = get_uniform_random(&mut rng);
+        }
+      }
+    }
+    sampler
+  }
+
+  pub fn next_sample(&mut self, rng: &mut RandGen) -> CameraSample {
+    let sample = match self.kind {
+      SamplerKind::Random => CameraSample {
+        pixel: (get_uniform_random(rng), get_uniform_random(rng)),
+        lens: (get_uniform_random(rng), get_uniform_random(rng)),
+      },
+      SamplerKind::Stratified => {
+        let n = (self.index as usize) % self.pixel_cells.len();
+        CameraSample {
+          pixel: self.jitter(self.pixel_cells[n], rng),
+          lens: self.jitter(self.lens_cells[n], rng),
+        }
+      }
+      SamplerKind::Halton => {
+        let value = |dimension: usize| {
+          let v =
+            radical_inverse(HALTON_BASES[dimension], self.index as u64) + self.offsets[dimension];
+          v - v.floor()
+        };
+        CameraSample {
+          pixel: (value(0), value(1)),
+          lens: (value(2), value(3)),
+        }
+      }
+    };
+    self.index += 1;
+    sample
+  }
+
+  // cell 番目のマスの中の一様な点
+  fn jitter(&self, cell: u32, rng: &mut RandGen) -> (f64, f64) {
+    let (nx, ny) = self.grid;
+    (
+      ((cell % nx) as f64 + get_uniform_random(rng)) / nx as f64,
+      ((cell / nx) as f64 + get_uniform_random(rng)) / ny as f64,
+    )
+  }
+}
+
+// index を base 進数で表し、小数点で折り返した値(van der Corput 列)
+fn radical_inverse(base: u32, mut index: u64) -> f64 {
+  let base = base as u64;
+  let inv_base = 1.0 / base as f64;
+  let mut inv = inv_base;
+  let mut result = 0.0;
+  while index > 0 {
+    result += (index % base) as f64 * inv;
+    index /= base;
+    inv *= inv_base;
+  }
+  result
+}
+
+#[cfg(test)]
+mod sampler_test {
+  use super::*;
+
+  #[test]
+  fn test_radical_inverse() {
+    assert_eq!(radical_inverse(2, 0), 0.0);
+    assert_eq!(radical_inverse(2, 1), 0.5);
+    assert_eq!(radical_inverse(2, 6), 0.375);
+    assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-12);
+  }
+
+  // 単位正方形を n x n のマスに区切り、点が入ったマスの数を数える
+  fn occupied_cells(points: &[(f64, f64)], n: usize) -> usize {
+    let mut cells = vec![false; n * n];
+    for (x, y) in points.iter() {
+      assert!((0.0..1.0).contains(x) && (0.0..1.0).contains(y));
+      cells[(*x * n as f64) as usize + (*y * n as f64) as usize * n] = true;
+    }
+    cells.iter().filter(|c| **c).count()
+  }
+
+  #[test]
+  fn test_stratified() {
+    // 16 サンプルなら 4x4 のマスのすべてに1つずつ入る
+    let mut rng = new_rand_gen(1);
+    let mut sampler = Sampler::new(SamplerKind::Stratified, 3, 0, 16);
+    let samples: Vec<CameraSample> = (0..16).map(|_| sampler.next_sample(&mut rng)).collect();
+    let pixels: Vec<(f64, f64)> = samples.iter().map(|s| s.pixel).collect();
+    let lenses: Vec<(f64, f64)> = samples.iter().map(|s| s.lens).collect();
+    assert_eq!(occupied_cells(&pixels, 4), 16);
+    assert_eq!(occupied_cells(&lenses, 4), 16);
+    // 画素とレンズで別の順番にする
+    assert_ne!(sampler.pixel_cells, sampler.lens_cells);
+  }
+
+  #[test]
+  fn test_halton() {
+    // 2回に分けて取っても、まとめて取った場合と同じ列になる
+    let mut rng = new_rand_gen(1);
+    let mut all = Sampler::new(SamplerKind::Halton, 5, 0, 8);
+    let mut first = Sampler::new(SamplerKind::Halton, 5, 0, 4);
+    let mut second = Sampler::new(SamplerKind::Halton, 5, 4, 4);
+    let expected: Vec<CameraSample> = (0..8).map(|_| all.next_sample(&mut rng)).collect();
+    let mut split: Vec<CameraSample> = (0..4).map(|_| first.next_sample(&mut rng)).collect();
+    split.extend((0..4).map(|_| second.next_sample(&mut rng)));
+    assert_eq!(expected, split);
+
+    // 各次元は底の累乗個のサンプルで、等分した区間に1つずつ入る
+    let mut sampler = Sampler::new(SamplerKind::Halton, 5, 0, 64);
+    let samples: Vec<CameraSample> = (0..64).map(|_| sampler.next_sample(&mut rng)).collect();
+    let bins = |values: Vec<f64>, n: usize| {
+      let mut bins: Vec<usize> = values.iter().map(|v| (v * n as f64) as usize).collect();
+      bins.sort();
+      bins.dedup();
+      bins.len()
+    };
+    assert_eq!(bins(samples.iter().map(|s| s.pixel.0).collect(), 64), 64);
+    assert_eq!(
+      bins(samples[..27].iter().map(|s| s.pixel.1).collect(), 27),
+      27
+    );
+    assert_eq!(
+      bins(samples[..25].iter().map(|s| s.lens.0).collect(), 25),
+      25
+    );
+    // 8x8 のマスも、独立な乱数(平均 41 マス)より多く埋まる
+    let pixels: Vec<(f64, f64)> = samples.iter().map(|s| s.pixel).collect();
+    assert!(occupied_cells(&pixels, 8) >= 46);
+  }
+}
@@ -0,0 +1,205 @@
+use crate::base::color::{srgb_encode, Color};
+use crate::base::vec::Vec3;
+
+// 線形の明るさを 0 から 1 の表示用の値に縮める方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapOperator {
+  // 何もしない。1 を超える明るさは白に飛ぶ
+  Clamp,
+  // L / (1 + L)。輝度 L を縮め、色の比率はそのまま保つ
+  Reinhard,
+  // ACES のフィルム調の曲線(Narkowicz による近似)。暗部を締め、明部をなだらかに飽和させる
+  Aces,
+  // AgX(Blender 4 の既定)を多項式で近似したもの。明るい色ほど白に寄せて色相の破綻を防ぐ
+  Agx,
+}
+
+// 表示用の値を 8bit に詰める前にかける伝達関数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferFunction {
+  // 1 / gamma 乗するだけの単純なガンマ補正
+  Gamma(f64),
+  // sRGB の規格どおりの区分的な曲線(暗部は線形)
+  Srgb,
+}
+
+// 線形な画像を 8bit の画像にするまでの処理。
+// 露出をかけ、トーンマッピングで 0 から 1 に縮め、伝達関数で表示用に符号化する
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapping {
+  // 露出補正(EV)。1 増やすごとに2倍明るくする
+  pub exposure: f64,
+  pub operator: ToneMapOperator,
+  pub transfer: TransferFunction,
+}
+
+impl Default for ToneMapping {
+  // 切り詰めてガンマ 2.2 をかける、これまでどおりの出力
+  fn default() -> Self {
+    Self {
+      exposure: 0.0,
+      operator: ToneMapOperator::Clamp,
+      transfer: TransferFunction::Gamma(2.2),
+    }
+  }
+}
+
+// AgX の色空間に入れる行列と、そこから戻す行列(行ごと)
+const AGX_INSET: [[f64; 3]; 3] = [
+  [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+  [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+  [0.0423756549057051, 0.0784336, 0.879142973793104],
+];
+const AGX_OUTSET: [[f64; 3]; 3] = [
+  [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+  [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+  [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+];
+// AgX で扱う明るさの範囲(中間灰色 0.18 からの EV)
+const AGX_MIN_EV: f64 = -12.47393;
+const AGX_MAX_EV: f64 = 4.026069;
+
+impl ToneMapping {
+  // 線形な色を 0 から 1 の表示用の値にする
+  pub fn apply(&self, color: Vec3) -> Vec3 {
+    let color = color * 2f64.powf(self.exposure);
+    let mapped = match self.operator {
+      ToneMapOperator::Clamp => color,
+      ToneMapOperator::Reinhard => {
+        let luminance = 0.2126 * color.get_x() + 0.7152 * color.get_y() + 0.0722 * color.get_z();
+        if luminance <= 0.0 {
+          color
+        } else {
+          color / (1.0 + luminance)
+        }
+      }
+      ToneMapOperator::Aces => Self::map_channels(color, |x| {
+        (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+      }),
+      ToneMapOperator::Agx => Self::agx(color),
+    };
+    Self::map_channels(mapped, |x| self.encode(x.clamp(0.0, 1.0)))
+  }
+
+  pub fn to_color(&self, color: Vec3) -> Color {
+    Color::from_vec3_gamma(self.apply(color), 255, 1.0)
+  }
+
+  fn encode(&self, x: f64) -> f64 {
+    match self.transfer {
+      TransferFunction::Gamma(gamma) => x.powf(1.0 / gamma),
+      TransferFunction::Srgb => srgb_encode(x),
+    }
+  }
+
+  fn agx(color: Vec3) -> Vec3 {
+    let inset = Self::multiply(&AGX_INSET, color);
+    // 対数をとって 0 から 1 に収め、S 字の曲線をかける
+    let curve = Self::map_channels(inset, |x| {
+      let x = (x.max(1e-10).log2().clamp(AGX_MIN_EV, AGX_MAX_EV) - AGX_MIN_EV)
+        / (AGX_MAX_EV - AGX_MIN_EV);
+      let x2 = x * x;
+      let x4 = x2 * x2;
+      15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+        - 0.00232
+    });
+    // 曲線の出力はガンマ 2.2 で符号化された値なので、線形に戻す
+    let outset = Self::multiply(&AGX_OUTSET, curve);
+    Self::map_channels(outset, |x| x.max(0.0).powf(2.2))
+  }
+
+  fn multiply(m: &[[f64; 3]; 3], v: Vec3) -> Vec3 {
+    let row = |r: &[f64; 3]| r[0] * v.get_x() + r[1] * v.get_y() + r[2] * v.get_z();
+    Vec3::new(row(&m[0]), row(&m[1]), row(&m[2]))
+  }
+
+  fn map_channels(v: Vec3, f: impl Fn(f64) -> f64) -> Vec3 {
+    Vec3::new(f(v.get_x()), f(v.get_y()), f(v.get_z()))
+  }
+}
+
+#[cfg(test)]
+mod tone_map_test {
+  use super::*;
+  use crate::base::color::srgb_decode;
+
+  fn tone_mapping(operator: ToneMapOperator) -> ToneMapping {
+    ToneMapping {
+      exposure: 0.0,
+      operator,
+      transfer: TransferFunction::Srgb,
+    }
+  }
+
+  #[test]
+  fn test_default_matches_gamma() {
+    // 既定の設定はこれまでの from_vec3_gamma と同じ色になる
+    let tone_mapping = ToneMapping::default();
+    for c in [
+      Vec3::new(0.0, 0.5, 1.0),
+      Vec3::new(0.02, 0.3, 7.0),
+      Vec3::new(-1.0, 0.999, 0.18),
+    ] {
+      assert_eq!(
+        tone_mapping.to_color(c).to_u32(),
+        Color::from_vec3_gamma(c, 255, 2.2).to_u32()
+      );
+    }
+  }
+
+  #[test]
+  fn test_exposure_and_srgb() {
+    let srgb = tone_mapping(ToneMapOperator::Clamp);
+    assert_eq!(srgb.apply(Vec3::zero_vector()), Vec3::zero_vector());
+    assert!((srgb.apply(Vec3::from_one(1.0)).get_x() - 1.0).abs() < 1e-12);
+    // 暗部は線形、0.5 は約 0.735
+    assert!((srgb.apply(Vec3::from_one(0.002)).get_x() - 0.02584).abs() < 1e-9);
+    assert!((srgb.apply(Vec3::from_one(0.5)).get_x() - 0.7354).abs() < 1e-3);
+    // 1 EV 上げると 2 倍の明るさと同じ
+    let brighter = ToneMapping {
+      exposure: 1.0,
+      ..srgb
+    };
+    assert_eq!(
+      brighter.apply(Vec3::from_one(0.2)),
+      srgb.apply(Vec3::from_one(0.4))
+    );
+    // 画像テクスチャの読み込みに使う逆変換で元に戻る
+    for x in [0.0, 0.002, 0.04, 0.18, 0.5, 1.0] {
+      let encoded = srgb.apply(Vec3::from_one(x)).get_x();
+      assert!((srgb_decode(encoded) - x).abs() < 1e-12, "{}", x);
+    }
+  }
+
+  #[test]
+  fn test_operators() {
+    for operator in [
+      ToneMapOperator::Reinhard,
+      ToneMapOperator::Aces,
+      ToneMapOperator::Agx,
+    ] {
+      let tone_mapping = tone_mapping(operator);
+      let value = |x: f64| tone_mapping.apply(Vec3::from_one(x)).get_y();
+      // 灰色は明るいほど明るく表示され、とても明るい値も白に飛ばない
+      let values: Vec<f64> = [0.0, 0.01, 0.18, 1.0, 4.0, 16.0]
+        .iter()
+        .map(|x| value(*x))
+        .collect();
+      assert!(values[0] < 0.05, "{:?}", operator);
+      assert!(values.windows(2).all(|w| w[0] < w[1]), "{:?}", operator);
+      assert!(values[4] < 1.0, "{:?}", operator);
+      // 中間灰色はおよそ中間の明るさになる
+      assert!((0.3..0.7).contains(&value(0.18)), "{:?}", operator);
+    }
+    // Reinhard は輝度 1 を半分にする
+    let reinhard = ToneMapping {
+      transfer: TransferFunction::Gamma(1.0),
+      ..tone_mapping(ToneMapOperator::Reinhard)
+    };
+    assert!((reinhard.apply(Vec3::from_one(1.0)).get_x() - 0.5).abs() < 1e-12);
+    // AgX はとても明るい色を白に寄せる
+    let agx = tone_mapping(ToneMapOperator::Agx);
+    let c = agx.apply(Vec3::new(64.0, 4.0, 1.0));
+    assert!(c.get_z() > 0.5);
+  }
+}